@@ -0,0 +1,463 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result, WrapErr};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use regex::Regex;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use structopt::StructOpt;
+use tracing::{info, warn};
+
+use crate::{
+    existing_node_indexes,
+    manifest::{NetworkManifest, NodeRecord},
+    node_dirs, node_index, node_name,
+    process::{self, Signal},
+    proxy, restart, supervisor,
+    wait::DEFAULT_JOIN_REGEX,
+    NetworkArgs,
+};
+
+/// File in the nodes dir every churn action is logged to
+const CHURN_LOG_FILENAME: &str = "churn.log";
+
+/// Time given to a killed node to be gone
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the churn loop checks whether it's been interrupted while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Churn a network started with `launch`, killing random nodes and adding new ones over time
+///
+/// Nodes are killed (never the genesis node) every `--kill-interval`, and new nodes are added
+/// every `--add-interval`, numbered after the existing ones and launched like the highest
+/// numbered of them. With `--restart` the killed nodes are restarted instead of new nodes being
+/// added. Every action is logged with a timestamp to `churn.log` in the nodes dir.
+#[derive(Debug, StructOpt)]
+pub struct Churn {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// How long to churn the network for, e.g. "10m"
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    duration: Duration,
+
+    /// Time between killing two nodes, e.g. "30s"
+    #[structopt(long, default_value = "30s", parse(try_from_str = humantime::parse_duration))]
+    kill_interval: Duration,
+
+    /// Time between adding (or restarting) two nodes, e.g. "30s"
+    #[structopt(long, default_value = "30s", parse(try_from_str = humantime::parse_duration))]
+    add_interval: Duration,
+
+    /// Restart killed nodes, from their root dirs, instead of adding new nodes
+    #[structopt(long)]
+    restart: bool,
+
+    /// Number of running nodes, the genesis node included, below which no node is killed
+    #[structopt(long, default_value = "2")]
+    min_nodes: usize,
+
+    /// Seed to pick the nodes to kill and restart at random with, to churn the network the same
+    /// way again. A random seed is used (and printed) by default.
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    /// Time given to each added or restarted node to join the network, e.g. "60s"
+    #[structopt(long, default_value = "60s", parse(try_from_str = humantime::parse_duration))]
+    join_timeout: Duration,
+
+    /// Regex matching the log line a node writes once it has joined the network
+    #[structopt(long, default_value = DEFAULT_JOIN_REGEX)]
+    join_regex: Regex,
+}
+
+/// Counts of the actions taken while churning
+#[derive(Debug, Default)]
+struct ChurnStats {
+    killed: usize,
+    added: usize,
+    restarted: usize,
+    failed: usize,
+}
+
+impl Churn {
+    /// Churn the network with these arguments.
+    pub fn run(&self) -> Result<()> {
+        if self.kill_interval.is_zero() || self.add_interval.is_zero() {
+            return Err(eyre!(
+                "The kill and add intervals must be greater than zero"
+            ));
+        }
+
+        let nodes_dir = self.network.nodes_dir()?;
+        let mut manifest = NetworkManifest::load(&nodes_dir)?;
+        supervisor::ensure_not_supervised(&manifest)?;
+        proxy::ensure_not_relayed(&manifest)?;
+        supervisor::handle_shutdown_signals()?;
+
+        let seed = self.seed.unwrap_or_else(rand::random);
+        println!("Churning the network with seed {}", seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut log = ChurnLog::open(&nodes_dir)?;
+        log.record(&format!(
+            "start seed={} duration={} kill-interval={} add-interval={} min-nodes={}{}",
+            seed,
+            humantime::format_duration(self.duration),
+            humantime::format_duration(self.kill_interval),
+            humantime::format_duration(self.add_interval),
+            self.min_nodes,
+            if self.restart { " restart" } else { "" }
+        ))?;
+
+        let started = Instant::now();
+        let mut next_kill = self.kill_interval;
+        let mut next_add = self.add_interval;
+        let mut stats = ChurnStats::default();
+        loop {
+            let next = next_kill.min(next_add);
+            if next > self.duration {
+                break;
+            }
+            if !sleep_until(started + next) {
+                log.record("interrupted")?;
+                break;
+            }
+
+            // Kills go first when both are due, so a restart can pick the node just killed
+            let result = if next_kill <= next_add {
+                next_kill += self.kill_interval;
+                self.kill_node(&nodes_dir, &mut manifest, &mut rng, &mut stats)
+            } else {
+                next_add += self.add_interval;
+                if self.restart {
+                    self.restart_node(&nodes_dir, &mut manifest, &mut rng, &mut stats)
+                } else {
+                    self.add_node(&nodes_dir, &mut manifest, &mut stats)
+                }
+            };
+
+            match result {
+                Ok(action) => {
+                    info!("{}", action);
+                    log.record(&action)?;
+                }
+                Err(error) => {
+                    warn!("{:?}", error);
+                    stats.failed += 1;
+                    log.record(&format!("failed {:#}", error))?;
+                }
+            }
+        }
+
+        log.record(&format!(
+            "stop killed={} added={} restarted={} failed={}",
+            stats.killed, stats.added, stats.restarted, stats.failed
+        ))?;
+        println!(
+            "Killed {} nodes, added {} and restarted {} in {} (see {})",
+            stats.killed,
+            stats.added,
+            stats.restarted,
+            humantime::format_duration(Duration::from_secs(started.elapsed().as_secs())),
+            log.path.display()
+        );
+
+        if stats.failed == 0 {
+            Ok(())
+        } else {
+            Err(eyre!("{} churn actions failed", stats.failed))
+        }
+    }
+
+    /// Kill a random running node, other than the genesis node, unless too few are left.
+    fn kill_node(
+        &self,
+        nodes_dir: &Path,
+        manifest: &mut NetworkManifest,
+        rng: &mut StdRng,
+        stats: &mut ChurnStats,
+    ) -> Result<String> {
+        let running: Vec<&NodeRecord> = manifest
+            .nodes
+            .iter()
+            .filter(|node| process::is_node_running(node.pid, &node.root_dir))
+            .collect();
+        if running.len() <= self.min_nodes {
+            return Ok(format!(
+                "skip kill, only {} nodes are running",
+                running.len()
+            ));
+        }
+
+        let candidates: Vec<&NodeRecord> = running
+            .into_iter()
+            .filter(|node| node_index(&node.name).is_some())
+            .collect();
+        let node = match candidates.choose(rng) {
+            Some(node) => (*node).clone(),
+            None => return Ok("skip kill, only the genesis node is running".to_string()),
+        };
+
+        process::signal_node(node.pid, Signal::Kill)?;
+        if !process::wait_for_exit(node.pid, &node.root_dir, KILL_TIMEOUT) {
+            return Err(eyre!(
+                "kill {} (pid {}): still running after {:?}",
+                node.name,
+                node.pid,
+                KILL_TIMEOUT
+            ));
+        }
+        stats.killed += 1;
+
+        // Killed nodes are kept in the manifest only to be restarted, their dirs are kept anyway
+        if self.restart {
+            if let Some(record) = manifest.node_mut(&node.name) {
+                record.exit_status = Some("killed by churn".to_string());
+            }
+        } else {
+            let _ = manifest.remove_node(&node.name);
+        }
+        manifest.save(nodes_dir)?;
+
+        Ok(format!("kill {} (pid {})", node.name, node.pid))
+    }
+
+    /// Add a node numbered after the existing ones, launched like the highest numbered node.
+    fn add_node(
+        &self,
+        nodes_dir: &Path,
+        manifest: &mut NetworkManifest,
+        stats: &mut ChurnStats,
+    ) -> Result<String> {
+        let template = manifest
+            .nodes
+            .iter()
+            .filter_map(|node| Some((node_index(&node.name)?, node)))
+            .max_by_key(|(idx, _)| *idx)
+            .map(|(_, node)| node.clone())
+            .ok_or_else(|| eyre!("add: there is no node to launch new nodes like"))?;
+
+        let idx = existing_node_indexes(&node_dirs(nodes_dir)?, manifest)
+            .into_iter()
+            .next_back()
+            .unwrap_or(1)
+            + 1;
+        let record = renamed_record(&template, &node_name(idx));
+        let name = record.name.clone();
+        if let Some(current_dir) = &record.current_dir {
+            fs::create_dir_all(current_dir).wrap_err_with(|| {
+                format!("add {}: failed to create {}", name, current_dir.display())
+            })?;
+        }
+
+        let _ = restart::start_recorded_node(
+            nodes_dir,
+            manifest,
+            record,
+            &[],
+            &self.join_regex,
+            self.join_timeout,
+        )
+        .wrap_err_with(|| format!("add {}", name))?;
+        stats.added += 1;
+
+        let pid = manifest
+            .node(&name)
+            .map(|node| node.pid)
+            .unwrap_or_default();
+        Ok(format!("add {} (pid {})", name, pid))
+    }
+
+    /// Restart a random node which is down, other than the genesis node.
+    fn restart_node(
+        &self,
+        nodes_dir: &Path,
+        manifest: &mut NetworkManifest,
+        rng: &mut StdRng,
+        stats: &mut ChurnStats,
+    ) -> Result<String> {
+        let down: Vec<&NodeRecord> = manifest
+            .nodes
+            .iter()
+            .filter(|node| node_index(&node.name).is_some())
+            .filter(|node| !process::is_node_running(node.pid, &node.root_dir))
+            .collect();
+        let record = match down.choose(rng) {
+            Some(node) => (*node).clone(),
+            None => return Ok("skip restart, no node is down".to_string()),
+        };
+        let name = record.name.clone();
+
+        let _ = restart::start_recorded_node(
+            nodes_dir,
+            manifest,
+            record,
+            &[],
+            &self.join_regex,
+            self.join_timeout,
+        )
+        .wrap_err_with(|| format!("restart {}", name))?;
+        stats.restarted += 1;
+
+        let pid = manifest
+            .node(&name)
+            .map(|node| node.pid)
+            .unwrap_or_default();
+        Ok(format!("restart {} (pid {})", name, pid))
+    }
+}
+
+/// Record for a new node named `name`, launched like the node of `template` but from its own
+/// root dir.
+fn renamed_record(template: &NodeRecord, name: &str) -> NodeRecord {
+    let root_dir = template.root_dir.with_file_name(name);
+    let old_root = template.root_dir.display().to_string();
+    let new_root = root_dir.display().to_string();
+    // Nodes run under `cargo flamegraph` write their graph (and run) after their name
+    let old_flamegraph = format!("-o {}-flame.svg", template.name);
+    let new_flamegraph = format!("-o {}-flame.svg", name);
+
+    let args = template
+        .args
+        .iter()
+        .map(|arg| {
+            if *arg == old_root {
+                new_root.clone()
+            } else if *arg == old_flamegraph {
+                new_flamegraph.clone()
+            } else {
+                arg.clone()
+            }
+        })
+        .collect();
+    // They also run from a dir of their own, named after them next to the nodes' flame dirs
+    let current_dir = template.current_dir.as_ref().map(|dir| {
+        if template.flame && dir.ends_with(&template.name) {
+            dir.with_file_name(name)
+        } else {
+            dir.clone()
+        }
+    });
+
+    NodeRecord {
+        name: name.to_string(),
+        root_dir,
+        args,
+        current_dir,
+        exit_status: None,
+        ..template.clone()
+    }
+}
+
+/// Sleep until `deadline`, returning `false` early if the launcher is asked to shut down.
+fn sleep_until(deadline: Instant) -> bool {
+    loop {
+        if supervisor::shutdown_requested() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+/// Log of the churn actions, appended to across runs
+struct ChurnLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl ChurnLog {
+    fn open(nodes_dir: &Path) -> Result<Self> {
+        let path = nodes_dir.join(CHURN_LOG_FILENAME);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self { path, file })
+    }
+
+    fn record(&mut self, action: &str) -> Result<()> {
+        writeln!(
+            self.file,
+            "{} {}",
+            humantime::format_rfc3339_millis(SystemTime::now()),
+            action
+        )
+        .wrap_err_with(|| format!("Failed to write to {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renamed_records_run_from_their_own_dirs() {
+        let template = NodeRecord {
+            name: "sn-node-4".to_string(),
+            pid: 4242,
+            root_dir: PathBuf::from("/tmp/nodes/sn-node-4"),
+            program: "cargo".to_string(),
+            args: vec![
+                "flamegraph".to_string(),
+                "-o sn-node-4-flame.svg".to_string(),
+                "--root-dir".to_string(),
+                "/tmp/nodes/sn-node-4".to_string(),
+            ],
+            envs: vec![],
+            current_dir: Some(PathBuf::from("/tmp/launched-from/sn-node-4")),
+            flame: true,
+            started_at: 1,
+            node_version: "sn_node 0.1.0".to_string(),
+            exit_status: Some("signal: 9 (SIGKILL)".to_string()),
+        };
+
+        let record = renamed_record(&template, "sn-node-7");
+        assert_eq!(record.name, "sn-node-7");
+        assert_eq!(record.root_dir, Path::new("/tmp/nodes/sn-node-7"));
+        assert_eq!(
+            record.args,
+            [
+                "flamegraph",
+                "-o sn-node-7-flame.svg",
+                "--root-dir",
+                "/tmp/nodes/sn-node-7"
+            ]
+        );
+        assert_eq!(
+            record.current_dir.as_deref(),
+            Some(Path::new("/tmp/launched-from/sn-node-7"))
+        );
+        assert!(record.exit_status.is_none());
+
+        // Nodes which don't run under `cargo flamegraph` keep the template's dir
+        let template = NodeRecord {
+            current_dir: Some(PathBuf::from("/tmp/sn-node-4")),
+            flame: false,
+            ..template
+        };
+        let record = renamed_record(&template, "sn-node-7");
+        assert_eq!(
+            record.current_dir.as_deref(),
+            Some(Path::new("/tmp/sn-node-4"))
+        );
+    }
+}
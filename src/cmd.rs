@@ -1,25 +1,41 @@
 use eyre::{eyre, Result, WrapErr};
 use std::{
     borrow::Cow,
+    env,
     ffi::{OsStr, OsString},
     fmt,
     net::SocketAddr,
-    path::Path,
-    process::{Command, Stdio},
-    thread,
-    time::Duration,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
 };
 use tracing::{debug, trace};
 
-const NODE_LIVENESS_TIMEOUT: Duration = Duration::from_secs(2);
+use crate::{
+    absolute_path,
+    manifest::{unix_time_now, NodeRecord},
+    process,
+};
+
+/// Args the launcher passes the nodes itself, so they can't be given as extra args
+const MANAGED_ARGS: &[&str] = &[
+    "--root-dir",
+    "--log-dir",
+    "--genesis-key",
+    "--hard-coded-contacts",
+    "--first",
+];
 
 #[derive(Clone)]
 pub(crate) struct NodeCmd<'a> {
     path: Cow<'a, OsStr>,
     envs: Vec<(Cow<'a, OsStr>, Cow<'a, OsStr>)>,
     args: NodeArgs<'a>,
+    // number of `-v`s to pass the node
+    verbosity: u8,
     // run w/ flamegraph
     flame: bool,
+    // args telling `cargo flamegraph` which sn_node to build, that of the current dir by default
+    flame_build_args: Vec<OsString>,
 }
 
 impl<'a> NodeCmd<'a> {
@@ -33,7 +49,9 @@ impl<'a> NodeCmd<'a> {
             path: into_cow_os_str(path),
             envs: Default::default(),
             args: Default::default(),
+            verbosity: 0,
             flame: false,
+            flame_build_args: vec![],
         }
     }
 
@@ -41,15 +59,33 @@ impl<'a> NodeCmd<'a> {
         Path::new(&self.path)
     }
 
+    pub(crate) fn set_path<P, Pb>(&mut self, path: P)
+    where
+        P: Into<Cow<'a, Pb>>,
+        Pb: AsRef<OsStr> + ToOwned + ?Sized + 'a,
+        Pb::Owned: Into<OsString>,
+    {
+        self.path = into_cow_os_str(path);
+    }
+
+    pub(crate) fn set_verbosity(&mut self, verbosity: u8) {
+        self.verbosity = verbosity
+    }
+
     pub(crate) fn set_flame(&mut self, flame: bool) {
         self.flame = flame
     }
 
+    /// Have `cargo flamegraph` build sn_node with these cargo args, e.g. from another checkout.
+    pub(crate) fn set_flame_build_args(&mut self, args: Vec<OsString>) {
+        self.flame_build_args = args
+    }
+
     pub(crate) fn gen_flamegraph(&self) -> bool {
         self.flame
     }
 
-    pub(crate) fn args(&self) -> &NodeArgs {
+    pub(crate) fn args(&self) -> &NodeArgs<'_> {
         &self.args
     }
 
@@ -66,6 +102,21 @@ impl<'a> NodeCmd<'a> {
             .push((into_cow_os_str(key), into_cow_os_str(value)));
     }
 
+    /// Set the env var `key`, replacing any value previously pushed for it.
+    pub(crate) fn set_env<K, Kb, V, Vb>(&mut self, key: K, value: V)
+    where
+        K: Into<Cow<'a, Kb>>,
+        Kb: AsRef<OsStr> + ToOwned + ?Sized + 'a,
+        Kb::Owned: Into<OsString>,
+        V: Into<Cow<'a, Vb>>,
+        Vb: AsRef<OsStr> + ToOwned + ?Sized + 'a,
+        Vb::Owned: Into<OsString>,
+    {
+        let key = into_cow_os_str(key);
+        self.envs.retain(|(existing, _)| *existing != key);
+        self.envs.push((key, into_cow_os_str(value)));
+    }
+
     pub(crate) fn push_arg<A, B>(&mut self, arg: A)
     where
         A: Into<Cow<'a, B>>,
@@ -77,7 +128,7 @@ impl<'a> NodeCmd<'a> {
 
     pub(crate) fn version(&self) -> Result<String> {
         let version = Command::new(&self.path)
-            .args(&["-V"])
+            .args(["-V"])
             .output()
             .map_or_else(
                 |error| Err(eyre!(error)),
@@ -110,18 +161,19 @@ impl<'a> NodeCmd<'a> {
         node_dir: &Path,
         contacts: &[SocketAddr],
         genesis_key: Option<&str>,
-    ) -> Result<()> {
+    ) -> Result<NodeProcess> {
         let node_dir = node_dir.join(node_name);
 
         let mut cmd = self.path().display().to_string();
 
         let flame_on = self.gen_flamegraph();
         let graph_output = format!("-o {}-flame.svg", node_name);
+        let flame_dir = absolute_path(Path::new(node_name))?;
 
         if flame_on {
             cmd = "cargo".to_string();
             // make a dir per node
-            std::fs::create_dir_all(node_name)?;
+            std::fs::create_dir_all(&flame_dir)?;
             debug!("Flame graph will be stored: {:?}", graph_output);
         }
 
@@ -131,7 +183,7 @@ impl<'a> NodeCmd<'a> {
         extra_args.push("--root-dir");
         extra_args.push(node_dir.clone());
         extra_args.push("--log-dir");
-        extra_args.push(node_dir);
+        extra_args.push(node_dir.clone());
 
         if let Some(genesis_key_str) = genesis_key {
             trace!("Network's genesis key: {}", genesis_key_str);
@@ -152,26 +204,42 @@ impl<'a> NodeCmd<'a> {
             );
         }
 
+        let mut all_args = vec![];
+        if flame_on {
+            // we set flamegraph to root as that's necesasry on mac
+            for arg in [
+                "flamegraph",
+                graph_output.as_str(),
+                "--root",
+                "--bin",
+                "sn_node",
+            ] {
+                all_args.push(into_cow_os_str(arg));
+            }
+            for arg in &self.flame_build_args {
+                all_args.push(into_cow_os_str(arg.as_os_str()));
+            }
+            all_args.push(into_cow_os_str("--"));
+        }
+        if self.verbosity > 0 {
+            all_args.push(into_cow_os_str(format!(
+                "-{}",
+                "v".repeat(self.verbosity as usize)
+            )));
+        }
+        all_args.extend(self.args.into_iter().cloned());
+        all_args.extend(extra_args.into_iter().cloned());
+
         let mut the_cmd = Command::new(cmd.clone());
-        let additonal_flame_args = vec![
-            "flamegraph",
-            &graph_output,
-            "--root",
-            "--bin",
-            "sn_node",
-            "--",
-        ];
         if flame_on {
             debug!("Launching nodes via `cargo flamegraph`");
             // we set the command ro run in each individal node dir (as each flamegraph uses a file `cargo-flamegraph.stacks` which cannot be renamed per per node)
-            // we set flamegraph to root as that's necesasry on mac
-            the_cmd
-                .current_dir(node_name)
-                .args(additonal_flame_args.clone());
+            the_cmd.current_dir(&flame_dir);
         }
-        the_cmd
-            .args(&self.args)
-            .args(&extra_args)
+        process::own_process_group(&mut the_cmd);
+        let started_at = unix_time_now();
+        let child = the_cmd
+            .args(&all_args)
             .envs(self.envs.iter().map(
                 // this looks like a no-op but really converts `&(_, _)` into `(_, _)`
                 |(key, value)| (key, value),
@@ -179,40 +247,138 @@ impl<'a> NodeCmd<'a> {
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
             .spawn()
-            .map_err(|error| eyre!(error))
-            .and_then(|mut child| {
-                // Wait a couple of seconds to see if the node fails immediately, so we can fail fast
-                thread::sleep(NODE_LIVENESS_TIMEOUT);
+            .wrap_err_with(|| format!("Failed to start '{}' with args '{:?}'", cmd, all_args))?;
+
+        Ok(NodeProcess {
+            child,
+            program: cmd,
+            args: all_args
+                .iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+            envs: self
+                .envs
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        key.to_string_lossy().into_owned(),
+                        value.to_string_lossy().into_owned(),
+                    )
+                })
+                .collect(),
+            current_dir: flame_on.then_some(flame_dir),
+            flame: flame_on,
+            root_dir: node_dir,
+            started_at,
+        })
+    }
+}
+
+/// Fail if any of the extra `args` to pass the nodes is one the launcher manages itself.
+///
+/// Only the long spellings of the managed args are recognised, alone or with an attached value
+/// (`--root-dir` or `--root-dir=<DIR>`). Short aliases sn_node may have for them are passed
+/// through unchecked, as sn_node's own flags aren't known to the launcher.
+pub(crate) fn ensure_not_managed(args: &[String]) -> Result<()> {
+    for arg in args {
+        let name = arg.split('=').next().unwrap_or_default();
+        if MANAGED_ARGS.contains(&name) {
+            return Err(eyre!(
+                "Node arg '{}' can't be given, it's set by the launcher itself",
+                arg
+            ));
+        }
+    }
+    Ok(())
+}
 
-                if let Some(status) = child.try_wait()? {
-                    return Err(eyre!("Node exited early (status: {})", status));
-                }
+/// A node process spawned by [`NodeCmd::run`], along with the exact command line it was started
+/// with.
+pub(crate) struct NodeProcess {
+    pub(crate) child: Child,
+    pub(crate) program: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) envs: Vec<(String, String)>,
+    pub(crate) current_dir: Option<PathBuf>,
+    pub(crate) flame: bool,
+    pub(crate) root_dir: PathBuf,
+    /// When the node was spawned, as seconds since the UNIX epoch
+    pub(crate) started_at: u64,
+}
 
-                Ok(())
+/// Fail unless the program `record` was started with can still be found, so a node isn't stopped
+/// to be restarted only to find it can't be started again.
+pub(crate) fn ensure_spawnable(record: &NodeRecord) -> Result<()> {
+    let program = Path::new(&record.program);
+    let found = if program.components().count() > 1 {
+        let program = match &record.current_dir {
+            Some(current_dir) => current_dir.join(program),
+            None => program.to_path_buf(),
+        };
+        is_executable(&program)
+    } else {
+        // Bare names are looked up in the PATH
+        env::var_os("PATH")
+            .map(|paths| {
+                env::split_paths(&paths).any(|dir| {
+                    let path = dir.join(program);
+                    is_executable(&path)
+                        || is_executable(&path.with_extension(env::consts::EXE_EXTENSION))
+                })
             })
-            .wrap_err_with(|| {
-                let mut all_args = vec![];
-                if flame_on {
-                    // all_args.extend(additonal_flame_args);
+            .unwrap_or(false)
+    };
 
-                    for arg in additonal_flame_args {
-                        let c = into_cow_os_str(arg);
-                        all_args.push(c);
-                    }
-                }
+    if found {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "{} can't be started again, its binary '{}' can't be found",
+            record.name,
+            record.program
+        ))
+    }
+}
 
-                for arg in self.args.into_iter() {
-                    all_args.push(arg.clone());
-                }
-                for arg in extra_args.into_iter() {
-                    all_args.push(arg.clone());
-                }
+/// Whether `path` is a file which can be run
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
 
-                format!("Failed to start '{}' with args '{:?}'", cmd, all_args)
-            })?;
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
 
-        Ok(())
+/// Spawn a node again with exactly the command line it was previously started with.
+pub(crate) fn spawn_recorded(record: &NodeRecord) -> Result<Child> {
+    trace!(
+        "Running '{}' with args {:?} ...",
+        record.program,
+        record.args
+    );
+
+    let mut cmd = Command::new(&record.program);
+    if let Some(current_dir) = &record.current_dir {
+        cmd.current_dir(current_dir);
     }
+    process::own_process_group(&mut cmd);
+
+    cmd.args(&record.args)
+        .envs(record.envs.iter().map(|(key, value)| (key, value)))
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .wrap_err_with(|| {
+            format!(
+                "Failed to start '{}' with args '{:?}'",
+                record.program, record.args
+            )
+        })
 }
 
 #[derive(Clone, Default)]
@@ -258,3 +424,54 @@ where
         Cow::Owned(val) => val.into().into(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn managed_args_are_rejected_in_their_long_forms() {
+        for arg in [
+            "--root-dir",
+            "--root-dir=/tmp/node",
+            "--first",
+            "--genesis-key=abc",
+        ] {
+            assert!(ensure_not_managed(&[arg.to_string()]).is_err(), "{}", arg);
+        }
+
+        let args = ["--max-capacity=1000", "--first-seen", "--clear-data"];
+        assert!(ensure_not_managed(&args.map(String::from)).is_ok());
+    }
+
+    fn record(program: &str) -> NodeRecord {
+        NodeRecord {
+            name: "sn-node-2".to_string(),
+            pid: 4242,
+            root_dir: PathBuf::from("/tmp/nodes/sn-node-2"),
+            program: program.to_string(),
+            args: vec![],
+            envs: vec![],
+            current_dir: None,
+            flame: false,
+            started_at: 1,
+            node_version: "sn_node 0.1.0".to_string(),
+            exit_status: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn nodes_are_only_spawnable_with_a_binary_which_can_be_run() -> Result<()> {
+        assert!(ensure_spawnable(&record("sh")).is_ok());
+        assert!(ensure_spawnable(&record("/bin/sh")).is_ok());
+        assert!(ensure_spawnable(&record("/nonexistent/sn_node")).is_err());
+        assert!(ensure_spawnable(&record("./fake_sn_node")).is_err());
+        assert!(ensure_spawnable(&record("no-such-sn-node")).is_err());
+
+        // Not executable
+        let file = tempfile::NamedTempFile::new()?;
+        assert!(ensure_spawnable(&record(&file.path().to_string_lossy())).is_err());
+        Ok(())
+    }
+}
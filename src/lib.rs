@@ -7,25 +7,64 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
+mod churn;
 mod cmd;
+mod logs;
+mod manifest;
+mod network;
+mod partition;
+mod pause;
+mod process;
+mod proxy;
+mod readiness;
+mod remove;
+mod restart;
+mod scenario;
+mod source;
+mod spec;
+mod status;
+mod stop;
+mod supervisor;
+mod upgrade;
+mod wait;
+
+pub use churn::Churn;
+pub use network::{Network, NetworkBuilder, Node};
+pub use partition::{Heal, Partition};
+pub use pause::{Freeze, Pause, Resume};
+pub use remove::Remove;
+pub use restart::Restart;
+pub use scenario::Scenario;
+pub use status::Status;
+pub use stop::{stop_network, Stop, StopOutcome, StoppedNode};
+pub use supervisor::RestartPolicy;
+pub use upgrade::Upgrade;
+pub use wait::Wait;
 
 use eyre::{eyre, Result, WrapErr};
 use std::{
-    borrow::Cow,
-    collections::HashSet,
+    collections::{BTreeSet, HashMap, HashSet},
     env,
+    ffi::OsString,
     fs::{self, File},
     io::BufReader,
-    net::SocketAddr,
-    ops::RangeInclusive,
-    path::PathBuf,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Component, Path, PathBuf},
+    process::Child,
     thread,
     time::Duration,
 };
 use structopt::StructOpt;
 use tracing::{debug, info};
 
-use cmd::NodeCmd;
+use cmd::{NodeCmd, NodeProcess};
+use manifest::{NetworkManifest, NodeRecord};
+use process::Signal;
+use proxy::Proxy;
+use readiness::{ReadinessProbe, NODE_LIVENESS_TIMEOUT};
+use regex::Regex;
+use spec::{LinkFaults, NetworkSpec, NodeMix, NodeOverride};
+use supervisor::Supervisor;
 
 #[cfg(not(target_os = "windows"))]
 const SN_NODE_EXECUTABLE: &str = "sn_node";
@@ -33,11 +72,77 @@ const SN_NODE_EXECUTABLE: &str = "sn_node";
 #[cfg(target_os = "windows")]
 const SN_NODE_EXECUTABLE: &str = "sn_node.exe";
 
-// Relative path from $HOME where to read the genesis node connection information from
+// Relative path from $HOME where the genesis node writes its connection information
 const GENESIS_CONN_INFO_FILEPATH: &str = ".safe/node/node_connection_info.config";
 
+// File name of the genesis node connection information copied into the nodes dir by default
+const CONN_INFO_FILENAME: &str = "node_connection_info.config";
+
 const DEFAULT_RUST_LOG: &str = "safe_network=debug";
 
+const GENESIS_NODE_NAME: &str = "sn-node-genesis";
+
+// Cargo profile sn_node is built with from source by default
+const DEFAULT_PROFILE: &str = "release";
+
+/// Tool to launch and manage Safe nodes
+#[derive(Debug, StructOpt)]
+pub enum Cmd {
+    /// Launch Safe nodes to form a local single-section network
+    Launch(Launch),
+    /// Run a Safe node to join an existing network
+    Join(Join),
+    /// Stop all the nodes of a network started with `launch`
+    Stop(Stop),
+    /// Report whether each of the nodes of a network started with `launch` is still running
+    Status(Status),
+    /// Wait until a number of the nodes of a network have joined it
+    Wait(Wait),
+    /// Upgrade the nodes of a network started with `launch` to another binary, one at a time
+    Upgrade(Upgrade),
+    /// Restart a node of a network started with `launch`, as it was launched
+    Restart(Restart),
+    /// Remove nodes from a network started with `launch`
+    Remove(Remove),
+    /// Churn a network started with `launch`, killing random nodes and adding new ones over time
+    Churn(Churn),
+    /// Split a network launched with `--proxy` into groups of nodes which can't reach each other
+    Partition(Partition),
+    /// Heal the partition of a network made with `partition`
+    Heal(Heal),
+    /// Pause nodes of a network started with `launch`, until they're resumed
+    Pause(Pause),
+    /// Resume nodes paused with `pause`
+    Resume(Resume),
+    /// Pause nodes of a network started with `launch` for a while
+    Freeze(Freeze),
+    /// Run the steps of a scenario file against a local network, reporting each step
+    Scenario(Scenario),
+}
+
+impl Cmd {
+    /// Run the selected subcommand.
+    pub fn run(&self) -> Result<()> {
+        match self {
+            Self::Launch(launch) => launch.run(),
+            Self::Join(join) => join.run(),
+            Self::Stop(stop) => stop.run(),
+            Self::Status(status) => status.run(),
+            Self::Wait(wait) => wait.run(),
+            Self::Upgrade(upgrade) => upgrade.run(),
+            Self::Restart(restart) => restart.run(),
+            Self::Remove(remove) => remove.run(),
+            Self::Churn(churn) => churn.run(),
+            Self::Partition(partition) => partition.run(),
+            Self::Heal(heal) => heal.run(),
+            Self::Pause(pause) => pause.run(),
+            Self::Resume(resume) => resume.run(),
+            Self::Freeze(freeze) => freeze.run(),
+            Self::Scenario(scenario) => scenario.run(),
+        }
+    }
+}
+
 /// Tool to launch Safe nodes to form a local single-section network
 ///
 /// Currently, this tool runs nodes on localhost (since that's the default if no IP address is given to the nodes)
@@ -46,9 +151,20 @@ pub struct Launch {
     #[structopt(flatten)]
     common: CommonArgs,
 
-    /// Interval in milliseconds between launching each of the nodes
-    #[structopt(short = "i", long, default_value = "100")]
-    interval: u64,
+    /// Network spec file (TOML, or JSON if its extension is `.json`) with the settings of the
+    /// network to launch, keyed by the name of the equivalent flags, e.g. `num-nodes = 11`. Flags
+    /// given on the command line override the settings in the file.
+    #[structopt(long)]
+    spec: Option<PathBuf>,
+
+    /// Print the effective network spec, i.e. the spec file merged with the command line flags
+    /// and defaults, and exit without launching anything
+    #[structopt(long)]
+    print_spec: bool,
+
+    /// Interval in milliseconds between launching each of the nodes [default: 100]
+    #[structopt(short = "i", long)]
+    interval: Option<u64>,
 
     /// Interval in seconds before deeming a peer to have timed out
     #[structopt(long = "idle-timeout-msec")]
@@ -58,93 +174,385 @@ pub struct Launch {
     #[structopt(long = "keep-alive-interval-msec")]
     keep_alive_interval_msec: Option<u64>,
 
-    /// Path where the output directories for all the nodes are written
-    #[structopt(short = "d", long, default_value = "./nodes")]
-    nodes_dir: PathBuf,
+    #[structopt(flatten)]
+    network: NetworkArgs,
 
     /// Number of nodes to spawn with the first one being the genesis. This number should be greater than 0.
-    #[structopt(short = "n", long, default_value = "15", env = "NODE_COUNT")]
-    num_nodes: usize,
+    /// The NODE_COUNT env var can be also used to set it, overriding a spec file. [default: 15]
+    #[structopt(short = "n", long)]
+    num_nodes: Option<usize>,
 
     /// IP used to launch the nodes with.
     #[structopt(long = "ip")]
     ip: Option<String>,
 
-    /// IP used to launch the nodes with.
+    /// Run a share of the nodes (other than the genesis node) with another sn_node binary, given
+    /// as `<PATH>=<PERCENT>`, e.g. `--node-mix ~/v2/sn_node=50%`. Can be repeated to mix in
+    /// several binaries, and the rest of the nodes run the one given with --node-path.
+    #[structopt(long = "node-mix", number_of_values = 1)]
+    node_mix: Vec<NodeMix>,
+
+    /// Run the nodes selected by number (the genesis node being #1) or name pattern with another
+    /// sn_node binary, given as `<NODES>=<PATH>`, e.g. `--node-path-for 'sn-node-[2-4]=~/v2/sn_node'`.
+    /// Can be repeated, and takes precedence over --node-mix.
+    #[structopt(
+        long = "node-path-for",
+        number_of_values = 1,
+        parse(try_from_str = spec::parse_node_path_override)
+    )]
+    node_paths_for: Vec<NodeOverride>,
+
+    /// Relay the traffic between the nodes through a UDP proxy on the nodes' IP in front of each
+    /// node, to inject faults into it. The launcher then stays in the foreground running the
+    /// proxies, as with --supervise.
+    #[structopt(long, overrides_with = "no-proxy")]
+    proxy: bool,
+
+    /// Don't relay the traffic between the nodes through proxies, even if a spec file or faults
+    /// would
+    #[structopt(long, overrides_with = "proxy")]
+    no_proxy: bool,
+
+    /// Faults to inject into the packets relayed between the nodes, given as
+    /// `[<FROM>-><TO>:]<FAULT>=<VALUE>,...`, e.g. `--faults latency-msec=50,jitter-msec=10` for all
+    /// the links or `--faults 'sn-node-[2-4]->1:loss=20'` for some. Faults are latency-msec,
+    /// jitter-msec, and the loss, duplicate and reorder percentages of the packets. Can be
+    /// repeated, and implies --proxy.
+    #[structopt(long, number_of_values = 1)]
+    faults: Vec<LinkFaults>,
+
+    /// Add nodes to the network already running in the nodes dir, instead of launching a new one.
+    /// New nodes are numbered after the highest numbered existing node.
     #[structopt(long = "add")]
     add_nodes_to_existing_network: bool,
+
+    /// When adding nodes, reuse the numbers of nodes which were removed from the network before
+    /// numbering new ones after the highest numbered existing node
+    #[structopt(long)]
+    fill_gaps: bool,
+
+    /// When adding nodes, reuse this node number (e.g. of a removed node). Can be repeated, and
+    /// counts towards the number of nodes to add.
+    #[structopt(long = "reuse-index", number_of_values = 1)]
+    reuse_indexes: Vec<usize>,
+
+    /// Keep running in the foreground once the nodes are launched, reaping and logging any nodes
+    /// which exit. Interrupting the launcher (e.g. with Ctrl-C) then shuts all the nodes down.
+    #[structopt(long)]
+    supervise: bool,
+
+    /// When to restart nodes which exit while supervised: never, on-failure or always
+    #[structopt(long, default_value = "never")]
+    restart: RestartPolicy,
+
+    /// Maximum number of times each supervised node is restarted
+    #[structopt(long, default_value = "5")]
+    max_restarts: u32,
+
+    /// Delay before restarting a supervised node, doubled on each subsequent restart of that
+    /// node, e.g. "1s"
+    #[structopt(long, default_value = "1s", parse(try_from_str = humantime::parse_duration))]
+    restart_backoff: Duration,
+
+    /// Path where the genesis node connection information is written to, and read from when
+    /// adding nodes. Defaults to a file in the nodes dir, so each network has its own.
+    #[structopt(long, env = "SN_CONN_INFO_PATH")]
+    conn_info_path: Option<PathBuf>,
+
+    /// Regex matching the log line which marks a node as ready, e.g. "Joined the network". When
+    /// given, each node is launched as soon as the previous one has logged such a line, and
+    /// --interval is ignored
+    #[structopt(long)]
+    ready_log_regex: Option<Regex>,
+
+    /// Time each node is given to be ready (i.e. for the genesis node to write its connection
+    /// info, or for a node to log a line matching --ready-log-regex), e.g. "60s"
+    #[structopt(long, default_value = "60s", parse(try_from_str = humantime::parse_duration))]
+    ready_timeout: Duration,
+
+    /// Time given to the nodes to exit when the launcher is interrupted before they are killed,
+    /// e.g. "10s"
+    #[structopt(long, default_value = "10s", parse(try_from_str = humantime::parse_duration))]
+    shutdown_timeout: Duration,
 }
 
 impl Launch {
     /// Launch a network with these arguments.
     pub fn run(&self) -> Result<()> {
-        let mut node_cmd = self.common.node_cmd()?;
+        self.launch(&[], false).map(|_| ())
+    }
 
-        if let Some(idle) = self.idle_timeout_msec {
+    /// Launch a network with these arguments on behalf of a `NetworkBuilder`, running the nodes
+    /// with the extra `node_envs` and leaving the handling of SIGINT and SIGTERM to the process
+    /// embedding the launcher. The launcher's env vars are ignored, the builder giving all the
+    /// settings.
+    ///
+    /// Returns the name and process of each of the nodes launched, left to the caller to reap.
+    pub(crate) fn run_embedded(
+        &self,
+        node_envs: &[(String, String)],
+    ) -> Result<Vec<(String, Child)>> {
+        self.launch(node_envs, true)
+    }
+
+    /// Launch a network with these arguments, running the nodes with the extra `node_envs`, and
+    /// shutting them down on SIGINT or SIGTERM unless `embedded`. Returns the processes of the
+    /// nodes unless the launcher stays in the foreground to supervise them.
+    fn launch(
+        &self,
+        node_envs: &[(String, String)],
+        embedded: bool,
+    ) -> Result<Vec<(String, Child)>> {
+        let mut spec = if embedded {
+            self.spec_with_env(|_| None)?
+        } else {
+            self.spec()?
+        };
+        if self.print_spec {
+            print!("{}", spec.resolved().to_toml()?);
+            return Ok(vec![]);
+        }
+
+        debug!("Network size: {} nodes", spec.num_nodes());
+
+        let nodes_dir = self.nodes_dir()?;
+        let mut manifest = if self.add_nodes_to_existing_network {
+            let manifest = NetworkManifest::load_or_new(&nodes_dir)?;
+            // The supervisor owns the manifest while it runs, and would drop the new nodes from it
+            supervisor::ensure_not_supervised(&manifest)?;
+            manifest
+        } else {
+            ensure_not_running(&nodes_dir)?;
+            NetworkManifest::new()
+        };
+        manifest.network_name = self.network.network_name.clone();
+        let node_ids = self.node_ids(&manifest, spec.num_nodes())?;
+        let mut proxy = if spec.proxy() {
+            Some(self.proxy(&spec, &node_ids)?)
+        } else {
+            None
+        };
+        // The proxies only run as long as the launcher does, and `cargo flamegraph` only writes
+        // out its graph when interrupted, which the launcher passes on to it
+        let foreground = self.supervise || proxy.is_some() || self.common.flame;
+        manifest.proxied = proxy.is_some();
+
+        // Nodes explicitly given a binary of their own run it rather than that of the mix
+        let node_mix = spec.node_mix_overrides(&node_ids)?;
+        spec.nodes = node_mix.into_iter().chain(spec.nodes.drain(..)).collect();
+
+        let mut node_cmd = self.common.node_cmd(&spec)?;
+        for (key, value) in node_envs {
+            node_cmd.push_env(key, value);
+        }
+
+        if let Some(idle) = spec.idle_timeout_msec {
             node_cmd.push_arg("--idle-timeout-msec");
             node_cmd.push_arg(idle.to_string());
         }
 
-        if let Some(keep_alive_interval_msec) = self.keep_alive_interval_msec {
+        if let Some(keep_alive_interval_msec) = spec.keep_alive_interval_msec {
             node_cmd.push_arg("--keep-alive-interval-msec");
             node_cmd.push_arg(keep_alive_interval_msec.to_string());
         }
 
-        if self.common.is_local {
+        if spec.local() {
             node_cmd.push_arg("--skip-auto-port-forwarding");
         }
 
-        if let Some(ip) = &self.ip {
-            node_cmd.push_arg("--local-addr");
-            node_cmd.push_arg(format!("{}:0", ip));
-        } else if self.common.is_local {
-            node_cmd.push_arg("--local-addr");
-            node_cmd.push_arg("127.0.0.1:0");
+        // Proxied nodes are each given the addresses of their own proxy instead
+        if proxy.is_none() {
+            if let Some(ip) = &spec.ip {
+                node_cmd.push_arg("--local-addr");
+                node_cmd.push_arg(format!("{}:0", ip));
+            } else if spec.local() {
+                node_cmd.push_arg("--local-addr");
+                node_cmd.push_arg("127.0.0.1:0");
+            }
         }
 
-        debug!("Network size: {} nodes", self.num_nodes);
+        // Interrupting the launcher shuts down the nodes it launched instead of orphaning them
+        if !embedded {
+            supervisor::handle_shutdown_signals()?;
+        }
+        let mut supervisor = Supervisor::new(
+            nodes_dir.clone(),
+            manifest,
+            self.restart,
+            self.max_restarts,
+            self.restart_backoff,
+            self.shutdown_timeout,
+        );
 
-        let interval = Duration::from_millis(self.interval);
+        if let Some(proxy) = &mut proxy {
+            proxy.start(&nodes_dir)?;
+        }
 
-        if !self.add_nodes_to_existing_network {
-            self.run_genesis(&node_cmd)?;
-            thread::sleep(interval);
+        if let Err(error) =
+            self.launch_nodes(&node_cmd, &node_ids, &spec, proxy.as_ref(), &mut supervisor)
+        {
+            // Nor is anyone left to stop the nodes launched so far for a `NetworkBuilder`
+            if foreground || embedded || supervisor::shutdown_requested() {
+                supervisor.shutdown()?;
+            }
+            return Err(error);
+        }
+
+        info!(
+            "Done! Network manifest written to {}",
+            NetworkManifest::path(&nodes_dir).display()
+        );
+
+        if foreground {
+            supervisor.run()?;
+            return Ok(vec![]);
+        }
+
+        Ok(supervisor.into_children())
+    }
+
+    fn launch_nodes(
+        &self,
+        node_cmd: &NodeCmd,
+        node_ids: &[usize],
+        spec: &NetworkSpec,
+        proxy: Option<&Proxy>,
+        supervisor: &mut Supervisor,
+    ) -> Result<()> {
+        // Find out the version of each of the binaries up front, so a broken one fails the launch
+        // before any node is started
+        let mut versions = HashMap::new();
+        let mut node_cmd_for = |name: &str| -> Result<_> {
+            let mut cmd = spec.node_cmd(name, node_cmd)?;
+            if let Some(proxy) = proxy {
+                for arg in proxy.node_args(name)? {
+                    cmd.push_arg(arg);
+                }
+            }
+            let version = match versions.get(cmd.path()) {
+                Some(version) => String::clone(version),
+                None => {
+                    let version = cmd.version()?;
+                    let _ = versions.insert(cmd.path().to_path_buf(), version.clone());
+                    version
+                }
+            };
+            Ok((cmd, version))
+        };
+        let genesis_cmd = if self.add_nodes_to_existing_network {
+            None
+        } else {
+            Some(node_cmd_for(GENESIS_NODE_NAME)?)
+        };
+        let node_cmds = node_ids
+            .iter()
+            .map(|&i| Ok((i, node_cmd_for(&node_name(i))?)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut launched = vec![];
+
+        if let Some((genesis_cmd, version)) = genesis_cmd {
+            if let Some(proxy) = proxy {
+                proxy.release(GENESIS_NODE_NAME)?;
+            }
+            let genesis = self.run_genesis(&genesis_cmd)?;
+            supervisor.add(
+                NodeRecord::new(GENESIS_NODE_NAME, &genesis, &version),
+                genesis.child,
+            )?;
+            info!("Launched {} running {}", GENESIS_NODE_NAME, version);
+            launched.push((GENESIS_NODE_NAME.to_string(), version));
+            supervisor::ensure_not_interrupted()?;
 
             debug!("Genesis wait over...");
         }
 
-        // Fetch node_conn_info from $HOME/.safe/node/node_connection_info.config.
-        let (genesis_contact_info, genesis_key) = read_genesis_conn_info()?;
+        let (mut genesis_contact_info, genesis_key) =
+            read_genesis_conn_info(&self.conn_info_path()?)?;
+        if let Some(proxy) = proxy {
+            genesis_contact_info = proxy.relay_contacts(&genesis_contact_info)?;
+        }
+        supervisor.set_network_info(genesis_contact_info.clone(), genesis_key.clone())?;
 
         debug!(
             "Common node args for launching the network: {:?}",
             node_cmd.args()
         );
 
-        let node_ids = self.node_ids()?;
         if !node_ids.is_empty() {
             info!("Launching nodes {:?}", node_ids);
 
-            for i in node_ids {
-                self.run_node(&node_cmd, i, &genesis_contact_info, genesis_key.as_ref())?;
-                thread::sleep(interval);
+            for (i, (cmd, version)) in node_cmds {
+                if let Some(proxy) = proxy {
+                    proxy.release(&node_name(i))?;
+                }
+                let node = self.run_node(&cmd, i, &genesis_contact_info, genesis_key.as_ref())?;
+                supervisor.add(NodeRecord::new(&node_name(i), &node, &version), node.child)?;
+                info!("Launched {} running {}", node_name(i), version);
+                launched.push((node_name(i), version));
+                if self.ready_log_regex.is_none() {
+                    thread::sleep(spec.interval());
+                }
+                supervisor::ensure_not_interrupted()?;
+            }
+        }
+
+        // Make it easy to tell which nodes run which version when mixing several of them
+        if versions.len() > 1 {
+            for (name, version) in launched {
+                println!("{:<20} {}", name, version);
             }
         }
 
-        info!("Done!");
         Ok(())
     }
 
-    fn run_genesis(&self, node_cmd: &NodeCmd) -> Result<()> {
+    fn run_genesis(&self, node_cmd: &NodeCmd) -> Result<NodeProcess> {
         // Set genesis node's command arguments
         let mut genesis_cmd = node_cmd.clone();
         genesis_cmd.push_arg("--first");
 
+        // The genesis node writes its connection info under its $HOME, so point that at its own
+        // root dir to keep networks from clobbering each other's (or the user's) connection info.
+        // Under `cargo flamegraph` that would keep cargo from finding its toolchains though, so
+        // the genesis node then writes it into the user's home dir, as it would by default.
+        let written_conn_info_path = if genesis_cmd.gen_flamegraph() {
+            user_conn_info_path()?
+        } else {
+            let genesis_home = self.nodes_dir()?.join(GENESIS_NODE_NAME);
+            genesis_cmd.push_env("HOME", genesis_home.clone());
+            genesis_conn_info_path(&genesis_home)?
+        };
+
+        // The genesis node is ready once it has written the network's connection info
+        let probe = ReadinessProbe::file(&written_conn_info_path);
+
         // Let's launch genesis node now
         debug!("Launching genesis node (#1)...");
-        genesis_cmd.run("sn-node-genesis", &self.nodes_dir, &[], None)?;
+        let genesis = self.start_node(&genesis_cmd, GENESIS_NODE_NAME, &[], None, probe)?;
 
-        Ok(())
+        let conn_info_path = self.conn_info_path()?;
+        if let Some(parent) = conn_info_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&written_conn_info_path, &conn_info_path).wrap_err_with(|| {
+            format!(
+                "Failed to copy node connection information file to '{}'",
+                conn_info_path.display()
+            )
+        })?;
+        info!(
+            "Genesis node connection information written to {}",
+            conn_info_path.display()
+        );
+
+        Ok(genesis)
+    }
+
+    fn conn_info_path(&self) -> Result<PathBuf> {
+        match &self.conn_info_path {
+            Some(path) => Ok(path.clone()),
+            None => Ok(self.nodes_dir()?.join(CONN_INFO_FILENAME)),
+        }
     }
 
     fn run_node(
@@ -153,42 +561,176 @@ impl Launch {
         node_idx: usize,
         contacts: &[SocketAddr],
         genesis_key_str: &str,
-    ) -> Result<()> {
+    ) -> Result<NodeProcess> {
         if self.add_nodes_to_existing_network {
             debug!("Adding node #{}...", node_idx)
         } else {
             debug!("Launching node #{}...", node_idx)
         };
-        node_cmd.run(
-            &format!("sn-node-{}", node_idx),
-            &self.nodes_dir,
-            contacts,
-            Some(genesis_key_str),
-        )?;
 
-        Ok(())
+        let name = node_name(node_idx);
+        let probe = match &self.ready_log_regex {
+            Some(regex) => ReadinessProbe::log_line(&self.nodes_dir()?.join(&name), regex.clone())?,
+            None => ReadinessProbe::Delay(NODE_LIVENESS_TIMEOUT),
+        };
+
+        self.start_node(node_cmd, &name, contacts, Some(genesis_key_str), probe)
+    }
+
+    /// Run a node and wait for it to be ready, killing it if it never is.
+    fn start_node(
+        &self,
+        node_cmd: &NodeCmd,
+        name: &str,
+        contacts: &[SocketAddr],
+        genesis_key: Option<&str>,
+        probe: ReadinessProbe,
+    ) -> Result<NodeProcess> {
+        let mut node = node_cmd.run(name, &self.nodes_dir()?, contacts, genesis_key)?;
+
+        if let Err(error) =
+            readiness::wait_until_ready(name, &mut node.child, probe, self.ready_timeout)
+        {
+            if let Err(error) = process::signal_node(node.child.id(), Signal::Kill) {
+                debug!("{:?}", error);
+            }
+            let _ = node.child.wait();
+            return Err(error);
+        }
+
+        Ok(node)
     }
 
-    fn node_ids(&self) -> Result<RangeInclusive<usize>> {
-        let paths =
-            fs::read_dir(&self.nodes_dir).wrap_err("Could not read existing testnet log dir")?;
+    fn nodes_dir(&self) -> Result<PathBuf> {
+        self.network.nodes_dir()
+    }
 
-        let count = paths
-            .collect::<Result<Vec<_>, _>>()
-            .wrap_err("Error collecting testnet log dir")?
-            .len();
+    /// Settings of the network to launch: those of the spec file overridden by the env vars, and
+    /// then by the command line flags, with the binaries the nodes run made absolute.
+    fn spec(&self) -> Result<NetworkSpec> {
+        self.spec_with_env(|name| env::var_os(name))
+    }
 
-        if count == 0 {
-            return Err(eyre!("A genesis node could not be found."));
+    /// Settings of the network to launch, with the env vars looked up with `var`.
+    fn spec_with_env<F>(&self, var: F) -> Result<NetworkSpec>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let file_spec = match &self.spec {
+            Some(path) => NetworkSpec::load(path)?,
+            None => NetworkSpec::default(),
+        };
+
+        file_spec
+            .merge(NetworkSpec::from_env(var)?)
+            .merge(NetworkSpec {
+                num_nodes: self.num_nodes,
+                interval: self.interval,
+                ip: self.ip.clone(),
+                idle_timeout_msec: self.idle_timeout_msec,
+                keep_alive_interval_msec: self.keep_alive_interval_msec,
+                proxy: switch(self.proxy, self.no_proxy),
+                node_mix: self.node_mix.clone(),
+                nodes: self.node_paths_for.clone(),
+                faults: self.faults.clone(),
+                ..self.common.spec()
+            })
+            .with_absolute_node_paths()
+    }
+
+    /// Proxies to put in front of the genesis node and the nodes numbered `node_ids`.
+    fn proxy(&self, spec: &NetworkSpec, node_ids: &[usize]) -> Result<Proxy> {
+        if self.add_nodes_to_existing_network {
+            return Err(eyre!(
+                "Nodes can't be added behind proxies, which are run by the launcher which launched the network"
+            ));
+        }
+        proxy::ensure_not_proxied(&spec.args)?;
+        for node in &spec.nodes {
+            proxy::ensure_not_proxied(&node.args)?;
         }
 
-        let last_idx: usize = if self.add_nodes_to_existing_network {
-            count + self.num_nodes
-        } else {
-            self.num_nodes
+        let ip = match &spec.ip {
+            Some(ip) => ip
+                .parse()
+                .wrap_err_with(|| format!("Invalid IP '{}' to run the proxies on", ip))?,
+            None => IpAddr::V4(Ipv4Addr::LOCALHOST),
         };
+        let names: Vec<String> = std::iter::once(GENESIS_NODE_NAME.to_string())
+            .chain(node_ids.iter().map(|&idx| node_name(idx)))
+            .collect();
+        Proxy::bind(ip, &names, &spec.faults)
+    }
 
-        Ok(count + 1..=last_idx)
+    /// Indexes of the nodes to launch, derived from the numbered node dirs and the manifest of the
+    /// network when adding nodes to it.
+    fn node_ids(&self, manifest: &NetworkManifest, num_nodes: usize) -> Result<Vec<usize>> {
+        if !self.add_nodes_to_existing_network {
+            if !self.reuse_indexes.is_empty() || self.fill_gaps {
+                return Err(eyre!(
+                    "--reuse-index and --fill-gaps can only be used when adding nodes (--add)"
+                ));
+            }
+
+            // The genesis node is #1
+            return Ok((2..=num_nodes).collect());
+        }
+
+        let nodes_dir = self.nodes_dir()?;
+        let node_dirs = node_dirs(&nodes_dir)?;
+        if manifest.node(GENESIS_NODE_NAME).is_none()
+            && !node_dirs.iter().any(|(name, _)| name == GENESIS_NODE_NAME)
+        {
+            return Err(eyre!("A genesis node could not be found."));
+        }
+
+        let existing = existing_node_indexes(&node_dirs, manifest);
+
+        if self.reuse_indexes.len() > num_nodes {
+            return Err(eyre!(
+                "Asked to reuse {} node numbers, but to add only {} nodes",
+                self.reuse_indexes.len(),
+                num_nodes
+            ));
+        }
+
+        let mut ids = vec![];
+        for &idx in &self.reuse_indexes {
+            if idx < 2 {
+                return Err(eyre!(
+                    "Node number {} is reserved for the genesis node",
+                    idx
+                ));
+            }
+            if ids.contains(&idx) {
+                return Err(eyre!("Node number {} to reuse given more than once", idx));
+            }
+            if let Some(node) = manifest.node(&node_name(idx)) {
+                if process::is_node_running(node.pid, &node.root_dir) {
+                    return Err(eyre!(
+                        "Can't reuse node number {}, {} is still running",
+                        idx,
+                        node.name
+                    ));
+                }
+            }
+            ids.push(idx);
+        }
+
+        let last_idx = existing.iter().next_back().copied().unwrap_or(1);
+        if self.fill_gaps {
+            let gaps = (2..last_idx).filter(|idx| !existing.contains(idx) && !ids.contains(idx));
+            let room = num_nodes - ids.len();
+            ids.extend(gaps.take(room).collect::<Vec<_>>());
+        }
+
+        let mut next_idx = last_idx.max(ids.iter().copied().max().unwrap_or(1)) + 1;
+        while ids.len() < num_nodes {
+            ids.push(next_idx);
+            next_idx += 1;
+        }
+
+        Ok(ids)
     }
 }
 
@@ -235,21 +777,26 @@ pub struct Join {
 impl Join {
     /// Join a network with these arguments.
     pub fn run(&self) -> Result<()> {
-        let mut node_cmd = self.common.node_cmd()?;
+        let env_spec = NetworkSpec {
+            node_path: spec::env_node_path(),
+            ..NetworkSpec::default()
+        };
+        let spec = env_spec.merge(self.common.spec());
+        let mut node_cmd = self.common.node_cmd(&spec)?;
 
         if let Some(max_capacity) = self.max_capacity {
             node_cmd.push_arg("--max-capacity");
             node_cmd.push_arg(max_capacity.to_string());
         }
 
-        if self.common.is_local || self.skip_auto_port_forwarding {
+        if spec.local() || self.skip_auto_port_forwarding {
             node_cmd.push_arg("--skip-auto-port-forwarding");
         }
 
         if let Some(local_addr) = self.local_addr {
             node_cmd.push_arg("--local-addr");
             node_cmd.push_arg(local_addr.to_string());
-        } else if self.common.is_local {
+        } else if spec.local() {
             node_cmd.push_arg("--local-addr");
             node_cmd.push_arg("127.0.0.1:0");
         }
@@ -274,12 +821,19 @@ impl Join {
         );
 
         debug!("Launching node...");
-        node_cmd.run(
+        let mut node = node_cmd.run(
             "", // no name passed
             &self.nodes_dir,
             &self.hard_coded_contacts,
             Some(&self.genesis_key),
         )?;
+        // Wait a couple of seconds to see if the node fails immediately, so we can fail fast
+        readiness::wait_until_ready(
+            "Node",
+            &mut node.child,
+            ReadinessProbe::Delay(NODE_LIVENESS_TIMEOUT),
+            NODE_LIVENESS_TIMEOUT,
+        )?;
 
         debug!(
             "Node logs are being stored at: {}/sn_node.log<DATETIME>",
@@ -291,12 +845,95 @@ impl Join {
     }
 }
 
+/// Arguments locating a network launched by this tool
+#[derive(Debug, StructOpt)]
+pub(crate) struct NetworkArgs {
+    /// Path where the output directories for all the nodes are written
+    #[structopt(short = "d", long, default_value = "./nodes")]
+    nodes_dir: PathBuf,
+
+    /// Name of the network, to keep several networks apart on the same host. The network is then
+    /// written into a subdirectory of the nodes dir with this name. Node names are only unique
+    /// within a network, so nodes of different networks share the same names.
+    #[structopt(long, env = "SN_NETWORK_NAME", parse(try_from_str = parse_network_name))]
+    network_name: Option<String>,
+}
+
+impl NetworkArgs {
+    /// Absolute path of the directory holding the network's nodes, connection info and manifest.
+    /// The paths of the nodes recorded in the manifest derive from it, so they still hold when
+    /// the network is managed from another working directory.
+    pub(crate) fn nodes_dir(&self) -> Result<PathBuf> {
+        let nodes_dir = absolute_path(&self.nodes_dir)?;
+        match &self.network_name {
+            Some(name) => Ok(nodes_dir.join(name)),
+            None => Ok(nodes_dir),
+        }
+    }
+}
+
+fn parse_network_name(name: &str) -> Result<String> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(name.to_string())
+    } else {
+        Err(eyre!(
+            "Invalid network name '{}', only letters, digits, '-' and '_' are allowed",
+            name
+        ))
+    }
+}
+
+/// Fail if any of the nodes of a network previously launched into `nodes_dir` is still running.
+fn ensure_not_running(nodes_dir: &Path) -> Result<()> {
+    if !NetworkManifest::path(nodes_dir).exists() {
+        return Ok(());
+    }
+
+    let manifest = NetworkManifest::load(nodes_dir)?;
+    let running: Vec<&str> = manifest
+        .nodes
+        .iter()
+        .filter(|node| process::is_node_running(node.pid, &node.root_dir))
+        .map(|node| node.name.as_str())
+        .collect();
+
+    if running.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "A network is already running in '{}' ({} nodes running: {}), stop it first or use \
+             a different --network-name",
+            nodes_dir.display(),
+            running.len(),
+            running.join(", ")
+        ))
+    }
+}
+
 #[derive(Debug, StructOpt)]
 struct CommonArgs {
     /// Path where to locate sn_node/sn_node.exe binary. The SN_NODE_PATH env var can be also used to set the path
-    #[structopt(short = "p", long, env = "SN_NODE_PATH")]
+    #[structopt(short = "p", long)]
     node_path: Option<PathBuf>,
 
+    /// Build sn_node with `cargo build` from this source checkout (e.g. of safe_network) and run
+    /// the nodes with it, instead of a prebuilt binary
+    #[structopt(long, conflicts_with = "node-path")]
+    from_source: Option<PathBuf>,
+
+    /// Cargo profile to build sn_node with when building it from source, e.g. "dev" [default:
+    /// release]
+    #[structopt(long, requires = "from-source")]
+    profile: Option<String>,
+
+    /// Cargo feature to enable when building sn_node from source. Can be repeated.
+    #[structopt(long = "feature", number_of_values = 1, requires = "from-source")]
+    features: Vec<String>,
+
     /// Verbosity level for nodes logs (default: INFO)
     #[structopt(short = "y", long, parse(from_occurrences))]
     nodes_verbosity: u8,
@@ -306,26 +943,66 @@ struct CommonArgs {
     rust_log: Option<String>,
 
     /// Output logs in json format for easier processing.
-    #[structopt(long)]
+    #[structopt(long, overrides_with = "no-json-logs")]
     json_logs: bool,
 
+    /// Output logs in plain text, even if a spec file says otherwise.
+    #[structopt(long, overrides_with = "json-logs")]
+    no_json_logs: bool,
+
     /// Run the section locally.
-    #[structopt(long = "local")]
+    #[structopt(long = "local", overrides_with = "no-local")]
     is_local: bool,
 
-    /// Run the nodes using `cargo flamegraph` (which needs to be preinstalled.)
-    /// It is recommended to manually run `cargo flamegraph --root --bin=sn_node -- --first` to ensure
-    /// everything is built. (This command will fail dur to insufficient args, but that's okay, carry
-    /// testnetting w/ --flame thereafter)
+    /// Don't run the section locally, even if a spec file says otherwise.
+    #[structopt(long = "no-local", overrides_with = "local")]
+    no_local: bool,
+
+    /// Run the nodes using `cargo flamegraph` (which needs to be preinstalled), building sn_node
+    /// from the checkout given with --from-source, or else from the one the launcher is run in.
+    /// Each node writes its graph into a dir named after it in the current dir.
     #[structopt(long = "flame")]
     flame: bool,
+
+    /// Extra arg passed verbatim to the nodes, e.g. `--node-arg=--max-capacity=1000`. Can be
+    /// repeated.
+    #[structopt(long = "node-arg", number_of_values = 1, allow_hyphen_values = true)]
+    node_args: Vec<String>,
+
+    /// Extra args passed verbatim to the nodes, given after `--`
+    #[structopt(last = true)]
+    trailing_node_args: Vec<String>,
 }
 
 impl CommonArgs {
-    fn node_cmd(&self) -> Result<NodeCmd> {
-        let mut cmd = match self.node_path.as_deref() {
-            Some(p) => NodeCmd::new(p),
-            None => {
+    /// The settings given by these flags, leaving out those which weren't given.
+    fn spec(&self) -> NetworkSpec {
+        NetworkSpec {
+            node_path: self.node_path.clone(),
+            nodes_verbosity: (self.nodes_verbosity > 0).then_some(self.nodes_verbosity),
+            rust_log: self.rust_log.clone(),
+            json_logs: switch(self.json_logs, self.no_json_logs),
+            local: switch(self.is_local, self.no_local),
+            args: self
+                .node_args
+                .iter()
+                .chain(&self.trailing_node_args)
+                .cloned()
+                .collect(),
+            ..NetworkSpec::default()
+        }
+    }
+
+    /// Command running the nodes with the settings of `spec`.
+    fn node_cmd<'a>(&self, spec: &'a NetworkSpec) -> Result<NodeCmd<'a>> {
+        let mut cmd = match (&self.from_source, spec.node_path.as_deref()) {
+            (Some(source_dir), _) => NodeCmd::new(source::build_node(
+                source_dir,
+                self.profile.as_deref().unwrap_or(DEFAULT_PROFILE),
+                &self.features,
+            )?),
+            (None, Some(p)) => NodeCmd::new(p),
+            (None, None) => {
                 let mut path =
                     dirs_next::home_dir().ok_or_else(|| eyre!("Home directory not found"))?;
 
@@ -335,22 +1012,31 @@ impl CommonArgs {
             }
         };
 
-        let rust_log = self.rust_log();
+        let rust_log = spec.rust_log();
         info!("Using RUST_LOG '{}'", rust_log);
 
         cmd.push_env("RUST_LOG", rust_log);
-        cmd.push_arg(
-            // We need a minimum of INFO level for nodes verbosity,
-            // since the genesis node logs the contact info at INFO level
-            format!("-{}", "v".repeat(2 + self.nodes_verbosity as usize)),
-        );
+        cmd.set_verbosity(node_verbosity(spec.nodes_verbosity()));
 
-        if self.json_logs {
+        if spec.json_logs() {
             cmd.push_arg("--json-logs");
         }
 
+        cmd::ensure_not_managed(&spec.args)?;
+        for arg in &spec.args {
+            cmd.push_arg(arg.as_str());
+        }
+
         if self.flame {
             cmd.set_flame(self.flame);
+            // `cargo flamegraph` builds sn_node itself, from the same checkout
+            if let Some(source_dir) = &self.from_source {
+                cmd.set_flame_build_args(source::cargo_args(
+                    source_dir,
+                    self.profile.as_deref().unwrap_or(DEFAULT_PROFILE),
+                    &self.features,
+                ));
+            }
         }
 
         debug!(
@@ -361,23 +1047,127 @@ impl CommonArgs {
 
         Ok(cmd)
     }
+}
+
+/// Number of `-v`s to run the nodes with for the given `--nodes-verbosity`
+fn node_verbosity(nodes_verbosity: u8) -> u8 {
+    // We need a minimum of INFO level for nodes verbosity,
+    // since the genesis node logs the contact info at INFO level
+    2 + nodes_verbosity
+}
+
+/// Setting given by a pair of `--<flag>`/`--no-<flag>` switches, if either was given.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+fn node_name(node_idx: usize) -> String {
+    format!("sn-node-{}", node_idx)
+}
+
+/// Index of the node named `name`, if it's a numbered node
+fn node_index(name: &str) -> Option<usize> {
+    name.strip_prefix("sn-node-")?.parse().ok()
+}
+
+/// Parse the name of a node, given either in full (e.g. `sn-node-4`), by number (e.g. `4`, the
+/// genesis node being #1) or as `genesis`.
+fn parse_node_name(name: &str) -> Result<String> {
+    match name.parse::<usize>() {
+        Ok(1) => Ok(GENESIS_NODE_NAME.to_string()),
+        Ok(idx) if idx > 1 => Ok(node_name(idx)),
+        Ok(_) => Err(eyre!(
+            "Invalid node number {}, nodes are numbered from 1",
+            name
+        )),
+        Err(_) if name == "genesis" || name == GENESIS_NODE_NAME => {
+            Ok(GENESIS_NODE_NAME.to_string())
+        }
+        Err(_) if node_index(name).filter(|idx| *idx > 1).is_some() => Ok(name.to_string()),
+        Err(_) => Err(eyre!(
+            "Invalid node '{}', expected e.g. sn-node-4, 4 or genesis",
+            name
+        )),
+    }
+}
 
-    fn rust_log(&self) -> Cow<'_, str> {
-        match self.rust_log.as_deref() {
-            Some(rust_log_flag) => rust_log_flag.into(),
-            None => match env::var("RUST_LOG") {
-                Ok(rust_log_env) => rust_log_env.into(),
-                Err(_) => DEFAULT_RUST_LOG.into(),
-            },
+/// Numbers of the nodes of a network, taken from its node dirs as well as its manifest. Only
+/// numbered nodes count, i.e. not the genesis node.
+fn existing_node_indexes(
+    node_dirs: &[(String, PathBuf)],
+    manifest: &NetworkManifest,
+) -> BTreeSet<usize> {
+    node_dirs
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .chain(manifest.nodes.iter().map(|node| node.name.as_str()))
+        .filter_map(node_index)
+        .collect()
+}
+
+/// Name and path of each of the node directories in `nodes_dir`
+fn node_dirs(nodes_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut dirs = vec![];
+    for entry in fs::read_dir(nodes_dir).wrap_err("Could not read existing testnet log dir")? {
+        let entry = entry.wrap_err("Error collecting testnet log dir")?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.path().is_dir() && name.starts_with("sn-node-") {
+            dirs.push((name, entry.path()));
         }
     }
+    Ok(dirs)
+}
+
+/// Path where a genesis node run with `$HOME` set to `genesis_home` writes its connection info
+#[cfg(not(target_os = "windows"))]
+fn genesis_conn_info_path(genesis_home: &Path) -> Result<PathBuf> {
+    Ok(genesis_home.join(GENESIS_CONN_INFO_FILEPATH))
 }
 
-fn read_genesis_conn_info() -> Result<(Vec<SocketAddr>, String)> {
+/// Path where a genesis node writes its connection info. The home dir isn't taken from the
+/// environment on Windows, so this is always the user's.
+#[cfg(target_os = "windows")]
+fn genesis_conn_info_path(_genesis_home: &Path) -> Result<PathBuf> {
+    user_conn_info_path()
+}
+
+/// Path where a genesis node run with the user's home dir writes its connection info
+fn user_conn_info_path() -> Result<PathBuf> {
     let home_dir = dirs_next::home_dir().ok_or_else(|| eyre!("Home directory not found"))?;
-    let conn_info_path = home_dir.join(GENESIS_CONN_INFO_FILEPATH);
+    Ok(home_dir.join(GENESIS_CONN_INFO_FILEPATH))
+}
 
-    let file = File::open(&conn_info_path).wrap_err_with(|| {
+/// Path of a binary to run made absolute against the current dir, so it's still found when run
+/// again from another dir. Bare binary names are left as they are, to be looked up in the PATH.
+fn absolute_program(path: &Path) -> Result<PathBuf> {
+    if path.components().count() > 1 {
+        absolute_path(path)
+    } else {
+        Ok(path.to_path_buf())
+    }
+}
+
+/// `path` made absolute against the current dir, without any `.` components
+fn absolute_path(path: &Path) -> Result<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir()
+            .wrap_err("Failed to read current dir")?
+            .join(path)
+    };
+    Ok(path
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect())
+}
+
+fn read_genesis_conn_info(conn_info_path: &Path) -> Result<(Vec<SocketAddr>, String)> {
+    let file = File::open(conn_info_path).wrap_err_with(|| {
         format!(
             "Failed to open node connection information file at '{}'",
             conn_info_path.display()
@@ -400,3 +1190,257 @@ fn read_genesis_conn_info() -> Result<(Vec<SocketAddr>, String)> {
 
     Ok((contacts, genesis_key_str))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn nodes_dir_is_absolute() -> Result<()> {
+        let network = NetworkArgs {
+            nodes_dir: PathBuf::from("./nodes"),
+            network_name: Some("alpha".to_string()),
+        };
+        let nodes_dir = network.nodes_dir()?;
+
+        assert!(nodes_dir.is_absolute());
+        assert_eq!(nodes_dir, env::current_dir()?.join("nodes").join("alpha"));
+        Ok(())
+    }
+
+    fn launch(args: &[&str]) -> Launch {
+        Launch::from_iter_safe(std::iter::once("sn_launch_tool").chain(args.iter().copied()))
+            .expect("valid launch args")
+    }
+
+    /// Network with node dirs for nodes #1, #2, #4 and #5, and a manifest also recording #7
+    fn network_with_gaps() -> Result<(tempfile::TempDir, NetworkManifest)> {
+        let nodes_dir = tempfile::tempdir()?;
+        for name in [GENESIS_NODE_NAME, "sn-node-2", "sn-node-4", "sn-node-5"] {
+            fs::create_dir(nodes_dir.path().join(name))?;
+        }
+        let mut manifest = NetworkManifest::new();
+        manifest.add_node(NodeRecord {
+            name: "sn-node-7".to_string(),
+            pid: 0,
+            root_dir: nodes_dir.path().join("sn-node-7"),
+            program: "sn_node".to_string(),
+            args: vec![],
+            envs: vec![],
+            current_dir: None,
+            flame: false,
+            started_at: 0,
+            node_version: "sn_node 0.1.0".to_string(),
+            exit_status: None,
+        });
+        Ok((nodes_dir, manifest))
+    }
+
+    #[test]
+    fn new_networks_number_nodes_after_the_genesis_node() -> Result<()> {
+        let ids = launch(&[]).node_ids(&NetworkManifest::new(), 3)?;
+        assert_eq!(ids, vec![2, 3]);
+        assert!(launch(&["--fill-gaps"])
+            .node_ids(&NetworkManifest::new(), 3)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn added_nodes_are_numbered_after_the_highest_node() -> Result<()> {
+        let (nodes_dir, manifest) = network_with_gaps()?;
+        let dir = nodes_dir.path().to_string_lossy().into_owned();
+
+        let ids = launch(&["--add", "-d", &dir]).node_ids(&manifest, 2)?;
+        assert_eq!(ids, vec![8, 9]);
+        Ok(())
+    }
+
+    #[test]
+    fn added_nodes_fill_gaps_or_reuse_numbers() -> Result<()> {
+        let (nodes_dir, manifest) = network_with_gaps()?;
+        let dir = nodes_dir.path().to_string_lossy().into_owned();
+
+        let ids = launch(&["--add", "--fill-gaps", "-d", &dir]).node_ids(&manifest, 4)?;
+        assert_eq!(ids, vec![3, 6, 8, 9]);
+
+        let ids = launch(&["--add", "--reuse-index", "4", "-d", &dir]).node_ids(&manifest, 2)?;
+        assert_eq!(ids, vec![4, 8]);
+
+        let ids = launch(&["--add", "--reuse-index", "12", "-d", &dir]).node_ids(&manifest, 2)?;
+        assert_eq!(ids, vec![12, 13]);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_node_numbers_to_reuse_are_rejected() -> Result<()> {
+        let (nodes_dir, manifest) = network_with_gaps()?;
+        let dir = nodes_dir.path().to_string_lossy().into_owned();
+
+        // Reserved for the genesis node, given twice, and more of them than nodes to add
+        for (mut args, count) in [
+            (vec!["--reuse-index", "1"], 1),
+            (vec!["--reuse-index", "3", "--reuse-index", "3"], 2),
+            (vec!["--reuse-index", "3", "--reuse-index", "6"], 1),
+        ] {
+            args.extend(["--add", "-d", dir.as_str()]);
+            assert!(
+                launch(&args).node_ids(&manifest, count).is_err(),
+                "{:?}",
+                args
+            );
+        }
+
+        let empty = tempfile::tempdir()?;
+        let empty = empty.path().to_string_lossy().into_owned();
+        assert!(launch(&["--add", "-d", &empty])
+            .node_ids(&NetworkManifest::new(), 1)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn network_names_are_plain_words() {
+        assert_eq!(
+            parse_network_name("shard-1_a").ok().as_deref(),
+            Some("shard-1_a")
+        );
+        assert!(parse_network_name("").is_err());
+        assert!(parse_network_name("../escape").is_err());
+        assert!(parse_network_name("a b").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn absolute_path_is_relative_to_the_current_dir() -> Result<()> {
+        assert_eq!(
+            absolute_path(Path::new("./nodes/./sn-node-2"))?,
+            env::current_dir()?.join("nodes").join("sn-node-2")
+        );
+        assert_eq!(
+            absolute_path(Path::new("/tmp/nodes"))?,
+            PathBuf::from("/tmp/nodes")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn node_binaries_are_recorded_with_absolute_paths() -> Result<()> {
+        let spec = launch(&[
+            "-p",
+            "./fake_sn_node",
+            "--node-mix",
+            "v2/sn_node=50%",
+            "--node-path-for",
+            "3=../v3/sn_node",
+        ])
+        .spec()?;
+        let node_ids = [2, 3, 4];
+        let mut spec = NetworkSpec {
+            nodes: spec
+                .node_mix_overrides(&node_ids)?
+                .into_iter()
+                .chain(spec.nodes.clone())
+                .collect(),
+            ..spec
+        };
+        let node_cmd = NodeCmd::new(spec.node_path.take().expect("node path is given"));
+
+        // The program each node is recorded with is the path of its command
+        for name in ["sn-node-2", "sn-node-3", "sn-node-4"] {
+            let program = spec.node_cmd(name, &node_cmd)?.path().to_path_buf();
+            assert!(program.is_absolute(), "{} runs {}", name, program.display());
+        }
+        assert!(node_cmd.path().ends_with("fake_sn_node"));
+
+        // Bare binary names are looked up in the PATH rather than in the current dir
+        let spec = launch(&["-p", "sn_node"]).spec()?;
+        assert_eq!(spec.node_path, Some(PathBuf::from("sn_node")));
+        Ok(())
+    }
+
+    /// Spec file turning on the settings which can be turned off from the command line
+    fn spec_file() -> Result<tempfile::NamedTempFile> {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile()?;
+        writeln!(
+            file,
+            "num-nodes = 5\njson-logs = true\nlocal = true\nproxy = true"
+        )?;
+        Ok(file)
+    }
+
+    #[test]
+    fn flags_turn_off_settings_of_the_spec_file() -> Result<()> {
+        let file = spec_file()?;
+        let path = file.path().to_string_lossy();
+
+        let spec = launch(&["--spec", &path]).spec()?;
+        assert!(spec.json_logs() && spec.local() && spec.proxy());
+
+        let spec = launch(&[
+            "--spec",
+            &path,
+            "--no-json-logs",
+            "--no-local",
+            "--no-proxy",
+        ])
+        .spec()?;
+        assert!(!spec.json_logs() && !spec.local() && !spec.proxy());
+
+        // The last of a pair of switches wins
+        let spec = launch(&["--spec", &path, "--no-proxy", "--proxy"]).spec()?;
+        assert!(spec.proxy());
+        Ok(())
+    }
+
+    #[test]
+    fn env_vars_override_the_spec_file_but_not_the_flags() -> Result<()> {
+        let file = spec_file()?;
+        let path = file.path().to_string_lossy();
+
+        let node_count = |name: &str| (name == "NODE_COUNT").then(|| OsString::from("9"));
+
+        let from_env = launch(&["--spec", &path]).spec_with_env(node_count)?;
+        let from_flags = launch(&["--spec", &path, "-n", "7"]).spec_with_env(node_count)?;
+
+        assert_eq!(from_env.num_nodes(), 9);
+        assert_eq!(from_flags.num_nodes(), 7);
+        Ok(())
+    }
+
+    #[test]
+    fn help_gives_the_actual_defaults() {
+        let mut help = vec![];
+        Launch::clap()
+            .write_long_help(&mut help)
+            .expect("help is written");
+        let help = String::from_utf8_lossy(&help);
+
+        for default in [spec::DEFAULT_NUM_NODES as u64, spec::DEFAULT_INTERVAL_MSEC] {
+            assert!(
+                help.contains(&format!("[default: {}]", default)),
+                "no [default: {}] in the help",
+                default
+            );
+        }
+    }
+
+    #[test]
+    fn node_names_are_parsed_in_full_by_number_or_as_genesis() -> Result<()> {
+        for (name, parsed) in [
+            ("sn-node-4", "sn-node-4"),
+            ("4", "sn-node-4"),
+            ("1", GENESIS_NODE_NAME),
+            ("genesis", GENESIS_NODE_NAME),
+            (GENESIS_NODE_NAME, GENESIS_NODE_NAME),
+        ] {
+            assert_eq!(parse_node_name(name)?, parsed);
+        }
+
+        for name in ["0", "sn-node-0", "sn-node-1", "sn-node-x", "node-4", ""] {
+            assert!(parse_node_name(name).is_err(), "{:?}", name);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Helpers to read the logs nodes write into their log dir.
+
+use eyre::{Result, WrapErr};
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Prefix of the node log files, which get a date suffix as they are rotated
+const LOG_FILE_PREFIX: &str = "sn_node.log";
+
+/// How much of the end of a log file to read when looking for its last line
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// List the log files in `log_dir`, oldest first.
+pub(crate) fn log_files(log_dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(log_dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => {
+            return Err(error)
+                .wrap_err_with(|| format!("Failed to read log dir '{}'", log_dir.display()))
+        }
+    };
+
+    let mut files = vec![];
+    for entry in entries {
+        let entry = entry.wrap_err("Error collecting log dir")?;
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(LOG_FILE_PREFIX)
+        {
+            let modified = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, entry.path()));
+        }
+    }
+    files.sort();
+
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Last non-empty line of the most recent log file in `log_dir`.
+pub(crate) fn last_line(log_dir: &Path) -> Result<Option<String>> {
+    let path = match log_files(log_dir)?.pop() {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    let mut file = File::open(&path)
+        .wrap_err_with(|| format!("Failed to open log file '{}'", path.display()))?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))?;
+    let mut tail = vec![];
+    file.read_to_end(&mut tail)
+        .wrap_err_with(|| format!("Failed to read log file '{}'", path.display()))?;
+
+    Ok(String::from_utf8_lossy(&tail)
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map(str::to_string))
+}
+
+/// Timestamp a log line was written at, for both plain and JSON formatted logs.
+pub(crate) fn line_timestamp(line: &str) -> Option<SystemTime> {
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
+        return json
+            .get("timestamp")
+            .and_then(|timestamp| timestamp.as_str())
+            .and_then(|timestamp| {
+                humantime::parse_rfc3339_weak(timestamp.trim_end_matches('Z')).ok()
+            });
+    }
+
+    strip_ansi_codes(line)
+        .split_whitespace()
+        .find_map(|word| humantime::parse_rfc3339_weak(word.trim_end_matches('Z')).ok())
+}
+
+/// The message logged on a line, i.e. the `message` field of JSON formatted logs, or the whole line
+/// otherwise.
+pub(crate) fn line_message(line: &str) -> Cow<'_, str> {
+    serde_json::from_str::<serde_json::Value>(line)
+        .ok()
+        .and_then(|json| {
+            json.pointer("/fields/message")
+                .and_then(|message| message.as_str())
+                .map(|message| Cow::Owned(message.to_string()))
+        })
+        .unwrap_or(Cow::Borrowed(line))
+}
+
+/// Remove the colour codes from a log line
+fn strip_ansi_codes(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip everything up to and including the final byte of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Follows the log files in a log dir, yielding only the lines written after it was created.
+pub(crate) struct LogCursor {
+    log_dir: PathBuf,
+    // Read offset and not yet terminated line of each of the log files
+    files: Vec<(PathBuf, u64, String)>,
+}
+
+impl LogCursor {
+    /// Start following `log_dir` from the current end of its log files.
+    pub(crate) fn from_end(log_dir: &Path) -> Result<Self> {
+        let files = log_files(log_dir)?
+            .into_iter()
+            .map(|path| {
+                let len = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+                (path, len, String::new())
+            })
+            .collect();
+
+        Ok(Self {
+            log_dir: log_dir.to_path_buf(),
+            files,
+        })
+    }
+
+    /// Start following `log_dir` from the beginning of its log files.
+    pub(crate) fn from_start(log_dir: &Path) -> Self {
+        Self {
+            log_dir: log_dir.to_path_buf(),
+            files: vec![],
+        }
+    }
+
+    /// Complete lines written to the log files since the last call.
+    pub(crate) fn new_lines(&mut self) -> Result<Vec<String>> {
+        for path in log_files(&self.log_dir)? {
+            if !self.files.iter().any(|(known, _, _)| *known == path) {
+                self.files.push((path, 0, String::new()));
+            }
+        }
+
+        let mut lines = vec![];
+        for (path, offset, partial) in &mut self.files {
+            let mut file = match File::open(&path) {
+                Ok(file) => file,
+                // Rotated away in the meantime
+                Err(_) => continue,
+            };
+            file.seek(SeekFrom::Start(*offset))?;
+            let mut new = vec![];
+            let read = file
+                .read_to_end(&mut new)
+                .wrap_err_with(|| format!("Failed to read log file '{}'", path.display()))?;
+            *offset += read as u64;
+
+            partial.push_str(&String::from_utf8_lossy(&new));
+            if let Some(end) = partial.rfind('\n') {
+                lines.extend(partial[..end].lines().map(str::to_string));
+                partial.replace_range(..=end, "");
+            }
+        }
+
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &Path, content: &str) -> Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    #[test]
+    fn cursor_yields_only_new_complete_lines() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let log = dir.path().join("sn_node.log.2022");
+        append(&log, "before\n")?;
+
+        let mut cursor = LogCursor::from_end(dir.path())?;
+        assert!(cursor.new_lines()?.is_empty());
+
+        append(&log, "first\nsecond with")?;
+        assert_eq!(cursor.new_lines()?, vec!["first"]);
+        append(&log, "out end\n")?;
+        assert_eq!(cursor.new_lines()?, vec!["second without end"]);
+        Ok(())
+    }
+
+    #[test]
+    fn cursor_picks_up_rotated_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        append(&dir.path().join("sn_node.log.1"), "old\n")?;
+        append(&dir.path().join("other.log"), "not a node log\n")?;
+
+        let mut cursor = LogCursor::from_start(dir.path());
+        assert_eq!(cursor.new_lines()?, vec!["old"]);
+        append(&dir.path().join("sn_node.log.2"), "new\n")?;
+        assert_eq!(cursor.new_lines()?, vec!["new"]);
+        Ok(())
+    }
+
+    #[test]
+    fn last_line_skips_blank_lines() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(last_line(dir.path())?, None);
+        append(&dir.path().join("sn_node.log"), "one\ntwo\n\n")?;
+        assert_eq!(last_line(dir.path())?.as_deref(), Some("two"));
+        Ok(())
+    }
+
+    #[test]
+    fn messages_and_timestamps_are_read_from_plain_and_json_lines() {
+        let plain = "\u{1b}[2m2022-03-04T05:06:07.123456Z\u{1b}[0m INFO Joined the network";
+        let json = r#"{"timestamp":"2022-03-04T05:06:07.123456Z","fields":{"message":"Joined the network"}}"#;
+        let expected = humantime::parse_rfc3339_weak("2022-03-04T05:06:07.123456").ok();
+
+        assert_eq!(line_timestamp(plain), expected);
+        assert_eq!(line_timestamp(json), expected);
+        assert_eq!(line_message(plain), plain);
+        assert_eq!(line_message(json), "Joined the network");
+        assert_eq!(line_timestamp("no timestamp here"), None);
+    }
+}
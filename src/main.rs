@@ -8,7 +8,7 @@
 // Software.
 
 use eyre::Result;
-use sn_launch_tool::Launch;
+use sn_launch_tool::Cmd;
 use structopt::StructOpt;
 use tracing::debug;
 
@@ -16,7 +16,8 @@ fn main() -> Result<()> {
     color_eyre::install()?;
     tracing_subscriber::fmt::init();
 
-    debug!("Launching Safe nodes...");
+    let cmd = Cmd::from_args();
+    debug!("Running {:?}", cmd);
 
-    Launch::from_args().run()
+    cmd.run()
 }
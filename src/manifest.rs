@@ -0,0 +1,266 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::trace;
+
+use crate::cmd::NodeProcess;
+
+/// Version of the manifest format, bumped on any incompatible change.
+const MANIFEST_VERSION: u32 = 1;
+
+/// Name of the manifest file written into the nodes dir
+const MANIFEST_FILENAME: &str = "network.json";
+
+/// Record of a launched network, persisted as JSON in its nodes dir.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct NetworkManifest {
+    pub(crate) version: u32,
+    #[serde(default)]
+    pub(crate) network_name: Option<String>,
+    pub(crate) genesis_key: Option<String>,
+    pub(crate) contacts: Vec<SocketAddr>,
+    pub(crate) nodes: Vec<NodeRecord>,
+    /// PID of the launcher while it supervises the nodes in the foreground
+    #[serde(default)]
+    pub(crate) supervisor_pid: Option<u32>,
+    /// Start time of the supervising launcher, telling it apart from a process reusing its PID
+    #[serde(default)]
+    pub(crate) supervisor_start_time: Option<String>,
+    /// Whether the traffic between the nodes is relayed through proxies run by the launcher
+    #[serde(default)]
+    pub(crate) proxied: bool,
+}
+
+/// Everything needed to identify (and re-run) one of the nodes of a network.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct NodeRecord {
+    pub(crate) name: String,
+    pub(crate) pid: u32,
+    pub(crate) root_dir: PathBuf,
+    /// The program actually spawned, i.e. the `sn_node` binary or `cargo` when running w/ flamegraph
+    pub(crate) program: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) envs: Vec<(String, String)>,
+    pub(crate) current_dir: Option<PathBuf>,
+    /// Whether the node runs under `cargo flamegraph`
+    #[serde(default)]
+    pub(crate) flame: bool,
+    /// Seconds since the UNIX epoch
+    pub(crate) started_at: u64,
+    pub(crate) node_version: String,
+    /// How the node exited, if it did so while being supervised
+    #[serde(default)]
+    pub(crate) exit_status: Option<String>,
+}
+
+impl NetworkManifest {
+    pub(crate) fn new() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            network_name: None,
+            genesis_key: None,
+            contacts: vec![],
+            nodes: vec![],
+            supervisor_pid: None,
+            supervisor_start_time: None,
+            proxied: false,
+        }
+    }
+
+    pub(crate) fn path(nodes_dir: &Path) -> PathBuf {
+        nodes_dir.join(MANIFEST_FILENAME)
+    }
+
+    /// Load the manifest from `nodes_dir`.
+    pub(crate) fn load(nodes_dir: &Path) -> Result<Self> {
+        let path = Self::path(nodes_dir);
+        let file = File::open(&path)
+            .wrap_err_with(|| format!("Failed to open network manifest at '{}'", path.display()))?;
+        let manifest: Self = serde_json::from_reader(BufReader::new(file)).wrap_err_with(|| {
+            format!("Failed to parse network manifest at '{}'", path.display())
+        })?;
+
+        if manifest.version != MANIFEST_VERSION {
+            return Err(eyre!(
+                "Unsupported network manifest version {} at '{}' (expected {})",
+                manifest.version,
+                path.display(),
+                MANIFEST_VERSION
+            ));
+        }
+
+        Ok(manifest)
+    }
+
+    /// Load the manifest from `nodes_dir`, or start a new one if there is none yet.
+    pub(crate) fn load_or_new(nodes_dir: &Path) -> Result<Self> {
+        if Self::path(nodes_dir).exists() {
+            Self::load(nodes_dir)
+        } else {
+            Ok(Self::new())
+        }
+    }
+
+    /// Write the manifest into `nodes_dir`, replacing any previous one.
+    pub(crate) fn save(&self, nodes_dir: &Path) -> Result<()> {
+        fs::create_dir_all(nodes_dir)
+            .wrap_err_with(|| format!("Failed to create nodes dir at '{}'", nodes_dir.display()))?;
+
+        let path = Self::path(nodes_dir);
+        // Write to a temporary file first so readers never see a half-written manifest
+        let tmp_path = path.with_extension("json.tmp");
+        let file = File::create(&tmp_path).wrap_err_with(|| {
+            format!(
+                "Failed to create network manifest at '{}'",
+                tmp_path.display()
+            )
+        })?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)
+            .wrap_err("Failed to serialise network manifest")?;
+        writer.flush().wrap_err_with(|| {
+            format!(
+                "Failed to write network manifest at '{}'",
+                tmp_path.display()
+            )
+        })?;
+        fs::rename(&tmp_path, &path).wrap_err_with(|| {
+            format!("Failed to write network manifest at '{}'", path.display())
+        })?;
+
+        trace!("Network manifest written to {}", path.display());
+        Ok(())
+    }
+
+    /// Add a node record, replacing any previous record with the same name.
+    pub(crate) fn add_node(&mut self, record: NodeRecord) {
+        self.nodes.retain(|node| node.name != record.name);
+        self.nodes.push(record);
+    }
+
+    /// Remove the record of the node named `name`, returning it if there was one.
+    pub(crate) fn remove_node(&mut self, name: &str) -> Option<NodeRecord> {
+        let idx = self.nodes.iter().position(|node| node.name == name)?;
+        Some(self.nodes.remove(idx))
+    }
+
+    pub(crate) fn node(&self, name: &str) -> Option<&NodeRecord> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    pub(crate) fn node_mut(&mut self, name: &str) -> Option<&mut NodeRecord> {
+        self.nodes.iter_mut().find(|node| node.name == name)
+    }
+}
+
+impl NodeRecord {
+    pub(crate) fn new(name: &str, process: &NodeProcess, node_version: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            pid: process.child.id(),
+            root_dir: process.root_dir.clone(),
+            program: process.program.clone(),
+            args: process.args.clone(),
+            envs: process.envs.clone(),
+            current_dir: process.current_dir.clone(),
+            flame: process.flame,
+            started_at: process.started_at,
+            node_version: node_version.to_string(),
+            exit_status: None,
+        }
+    }
+}
+
+/// Current time as seconds since the UNIX epoch
+pub(crate) fn unix_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, root_dir: &Path) -> NodeRecord {
+        NodeRecord {
+            name: name.to_string(),
+            pid: 4242,
+            root_dir: root_dir.join(name),
+            program: "sn_node".to_string(),
+            args: vec![
+                "--root-dir".to_string(),
+                root_dir.join(name).display().to_string(),
+            ],
+            envs: vec![("RUST_LOG".to_string(), "safe_network=debug".to_string())],
+            current_dir: None,
+            flame: false,
+            started_at: 1,
+            node_version: "sn_node 0.1.0".to_string(),
+            exit_status: None,
+        }
+    }
+
+    #[test]
+    fn saved_manifest_loads_back() -> Result<()> {
+        let nodes_dir = tempfile::tempdir()?;
+        let mut manifest = NetworkManifest::new();
+        manifest.genesis_key = Some("genesis-key".to_string());
+        manifest.contacts = vec!["127.0.0.1:12000".parse()?];
+        manifest.add_node(record("sn-node-genesis", nodes_dir.path()));
+        manifest.add_node(record("sn-node-2", nodes_dir.path()));
+        manifest.save(nodes_dir.path())?;
+
+        let loaded = NetworkManifest::load(nodes_dir.path())?;
+        assert_eq!(loaded.genesis_key.as_deref(), Some("genesis-key"));
+        assert_eq!(loaded.contacts, manifest.contacts);
+        assert_eq!(loaded.nodes.len(), 2);
+        assert_eq!(
+            loaded.node("sn-node-2").map(|node| &node.root_dir),
+            Some(&nodes_dir.path().join("sn-node-2"))
+        );
+        assert!(!nodes_dir.path().join("network.json.tmp").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn adding_a_node_replaces_its_previous_record() {
+        let mut manifest = NetworkManifest::new();
+        manifest.add_node(record("sn-node-2", Path::new("/nodes")));
+        let mut restarted = record("sn-node-2", Path::new("/nodes"));
+        restarted.pid = 4343;
+        manifest.add_node(restarted);
+
+        assert_eq!(manifest.nodes.len(), 1);
+        assert_eq!(manifest.node("sn-node-2").map(|node| node.pid), Some(4343));
+        assert!(manifest.remove_node("sn-node-2").is_some());
+        assert!(manifest.node("sn-node-2").is_none());
+    }
+
+    #[test]
+    fn manifest_of_another_version_is_rejected() -> Result<()> {
+        let nodes_dir = tempfile::tempdir()?;
+        let mut manifest = NetworkManifest::new();
+        manifest.version = MANIFEST_VERSION + 1;
+        manifest.save(nodes_dir.path())?;
+
+        assert!(NetworkManifest::load(nodes_dir.path()).is_err());
+        Ok(())
+    }
+}
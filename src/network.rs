@@ -0,0 +1,499 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Library API to launch local networks and manage their nodes, e.g. from integration tests.
+
+use eyre::{eyre, Result, WrapErr};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::Child,
+    time::Duration,
+};
+use tracing::warn;
+
+use crate::{
+    absolute_path,
+    manifest::{NetworkManifest, NodeRecord},
+    parse_node_name,
+    process::{self, Signal},
+    restart,
+    stop::{self, stop_network, StopOutcome, StoppedNode},
+    supervisor::RestartPolicy,
+    wait::DEFAULT_JOIN_REGEX,
+    CommonArgs, Launch, NetworkArgs,
+};
+
+/// Time given to the nodes to exit gracefully before they are killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given to each node to be ready when launched, or to rejoin the network when restarted
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Builder of a local network, launched as with the `launch` subcommand
+///
+/// Unlike `launch`, the network isn't set up from the `NODE_COUNT` and `SN_NODE_PATH` env vars, so
+/// those of the process embedding the launcher can't change what the builder launches.
+///
+/// ```no_run
+/// # fn main() -> eyre::Result<()> {
+/// use sn_launch_tool::NetworkBuilder;
+///
+/// let mut network = NetworkBuilder::new()
+///     .num_nodes(11)
+///     .node_path("/usr/local/bin/sn_node")
+///     .nodes_dir("/tmp/nodes")
+///     .launch()?;
+/// network.kill_node("sn-node-4")?;
+/// let _ = network.add_nodes(2)?;
+/// network.shutdown()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct NetworkBuilder {
+    num_nodes: Option<usize>,
+    node_path: Option<PathBuf>,
+    nodes_dir: PathBuf,
+    interval: Option<Duration>,
+    ip: Option<String>,
+    envs: Vec<(String, String)>,
+    args: Vec<String>,
+}
+
+/// Handle on a network launched by a [`NetworkBuilder`]
+///
+/// The nodes keep running until the network is shut down with [`Network::shutdown`], or the handle
+/// is dropped.
+#[derive(Debug)]
+pub struct Network {
+    builder: NetworkBuilder,
+    manifest: NetworkManifest,
+    genesis_key: String,
+    /// Processes of the nodes launched through this handle, reaped once they exit
+    children: BTreeMap<String, Child>,
+    /// Whether the network was shut down, leaving nothing to stop when the handle is dropped
+    shut_down: bool,
+}
+
+/// Node of a [`Network`]
+#[derive(Clone, Debug)]
+pub struct Node {
+    /// Name of the node, e.g. `sn-node-4`
+    pub name: String,
+    /// PID the node was last started with
+    pub pid: u32,
+    /// Root dir of the node, holding its data and logs
+    pub root_dir: PathBuf,
+    /// Version of the sn_node binary the node runs, e.g. `sn_node 0.58.0`
+    pub version: String,
+}
+
+impl Default for NetworkBuilder {
+    fn default() -> Self {
+        Self {
+            num_nodes: None,
+            node_path: None,
+            nodes_dir: PathBuf::from("./nodes"),
+            interval: None,
+            ip: None,
+            envs: vec![],
+            args: vec![],
+        }
+    }
+}
+
+impl NetworkBuilder {
+    /// Builder of a network with the same defaults as `launch`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of nodes to launch, the genesis node included [default: 15]
+    pub fn num_nodes(mut self, num_nodes: usize) -> Self {
+        self.num_nodes = Some(num_nodes);
+        self
+    }
+
+    /// Path of the sn_node binary to run the nodes with [default: ~/.safe/node/sn_node]
+    pub fn node_path<P: Into<PathBuf>>(mut self, node_path: P) -> Self {
+        self.node_path = Some(node_path.into());
+        self
+    }
+
+    /// Path where the directories of the nodes are written [default: ./nodes]
+    pub fn nodes_dir<P: Into<PathBuf>>(mut self, nodes_dir: P) -> Self {
+        self.nodes_dir = nodes_dir.into();
+        self
+    }
+
+    /// Interval between launching each of the nodes [default: 100ms]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// IP to launch the nodes with.
+    pub fn ip<S: Into<String>>(mut self, ip: S) -> Self {
+        self.ip = Some(ip.into());
+        self
+    }
+
+    /// Env var to run the nodes with. Can be called repeatedly.
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Extra arg passed verbatim to the nodes. Can be called repeatedly.
+    pub fn arg<S: Into<String>>(mut self, arg: S) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Extra args passed verbatim to the nodes.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Launch the network, returning once all its nodes are launched.
+    pub fn launch(mut self) -> Result<Network> {
+        // Nodes are launched with their root dirs under the nodes dir, which the network has to
+        // keep finding wherever the current dir moves to
+        self.nodes_dir = absolute_path(&self.nodes_dir)?;
+        let children = self
+            .launch_cmd(self.num_nodes, false)
+            .run_embedded(&self.envs)?;
+
+        let manifest = NetworkManifest::load(&self.nodes_dir)?;
+        let genesis_key = manifest
+            .genesis_key
+            .clone()
+            .ok_or_else(|| eyre!("The genesis key of the network wasn't recorded"))?;
+        Ok(Network {
+            builder: self,
+            manifest,
+            genesis_key,
+            children: children.into_iter().collect(),
+            shut_down: false,
+        })
+    }
+
+    /// `launch` run with the settings of this builder, launching `num_nodes` nodes or adding them
+    /// to the network if `add`.
+    fn launch_cmd(&self, num_nodes: Option<usize>, add: bool) -> Launch {
+        Launch {
+            common: CommonArgs {
+                node_path: self.node_path.clone(),
+                from_source: None,
+                profile: None,
+                features: vec![],
+                nodes_verbosity: 0,
+                rust_log: None,
+                json_logs: false,
+                no_json_logs: false,
+                is_local: false,
+                no_local: false,
+                flame: false,
+                node_args: self.args.clone(),
+                trailing_node_args: vec![],
+            },
+            spec: None,
+            print_spec: false,
+            interval: self.interval.map(|interval| interval.as_millis() as u64),
+            idle_timeout_msec: None,
+            keep_alive_interval_msec: None,
+            network: NetworkArgs {
+                nodes_dir: self.nodes_dir.clone(),
+                network_name: None,
+            },
+            num_nodes,
+            ip: self.ip.clone(),
+            node_mix: vec![],
+            node_paths_for: vec![],
+            proxy: false,
+            no_proxy: false,
+            faults: vec![],
+            add_nodes_to_existing_network: add,
+            fill_gaps: false,
+            reuse_indexes: vec![],
+            supervise: false,
+            restart: RestartPolicy::Never,
+            max_restarts: 0,
+            restart_backoff: Duration::ZERO,
+            conn_info_path: None,
+            ready_log_regex: None,
+            ready_timeout: READY_TIMEOUT,
+            shutdown_timeout: STOP_TIMEOUT,
+        }
+    }
+}
+
+impl Network {
+    /// The nodes of the network, the genesis node first, including those which were killed.
+    pub fn nodes(&self) -> Vec<Node> {
+        self.manifest.nodes.iter().map(Node::from).collect()
+    }
+
+    /// Root dirs of the nodes of the network, in the same order as [`Network::nodes`].
+    pub fn root_dirs(&self) -> Vec<PathBuf> {
+        self.manifest
+            .nodes
+            .iter()
+            .map(|node| node.root_dir.clone())
+            .collect()
+    }
+
+    /// Contacts of the network, to join it or connect to it
+    pub fn contacts(&self) -> &[SocketAddr] {
+        &self.manifest.contacts
+    }
+
+    /// Genesis key of the network
+    pub fn genesis_key(&self) -> &str {
+        &self.genesis_key
+    }
+
+    /// Absolute path of the directory holding the network's nodes, connection info and manifest
+    pub fn nodes_dir(&self) -> &Path {
+        &self.builder.nodes_dir
+    }
+
+    /// Add `count` nodes to the network, launched like its first nodes, returning them.
+    pub fn add_nodes(&mut self, count: usize) -> Result<Vec<Node>> {
+        let existing: BTreeSet<_> = self
+            .manifest
+            .nodes
+            .iter()
+            .map(|node| node.name.clone())
+            .collect();
+        let children = self
+            .builder
+            .launch_cmd(Some(count), true)
+            .run_embedded(&self.builder.envs)?;
+        self.children.extend(children);
+        self.reload()?;
+
+        Ok(self
+            .manifest
+            .nodes
+            .iter()
+            .filter(|node| !existing.contains(&node.name))
+            .map(Node::from)
+            .collect())
+    }
+
+    /// Kill the node named `name` (e.g. `sn-node-4`, `4` or `genesis`) outright.
+    pub fn kill_node(&mut self, name: &str) -> Result<()> {
+        self.reap();
+        let node = self.running_node(name)?.clone();
+        if let Some(mut child) = self.children.remove(&node.name) {
+            // Waiting on the node both makes sure it's gone and reaps it
+            process::signal_node(child.id(), Signal::Kill)?;
+            let _ = child
+                .wait()
+                .wrap_err_with(|| format!("Failed to wait for {} to exit", node.name))?;
+            return Ok(());
+        }
+
+        match stop::kill_node(&node) {
+            StopOutcome::Killed => Ok(()),
+            _ => Err(eyre!(
+                "{} (pid {}) is still running after being killed",
+                node.name,
+                node.pid
+            )),
+        }
+    }
+
+    /// Restart the node named `name` (e.g. `sn-node-4` or `4`) with the same args, env and root
+    /// dir, stopping it first if it's still running, and wait for it to rejoin the network. The
+    /// genesis node can't be restarted, as it would found a new network instead.
+    pub fn restart_node(&mut self, name: &str) -> Result<()> {
+        let name = parse_node_name(name)?;
+        let record = self
+            .manifest
+            .node(&name)
+            .cloned()
+            .ok_or_else(|| eyre!("There is no {} in the network", name))?;
+        let join_regex = Regex::new(DEFAULT_JOIN_REGEX).wrap_err("Invalid join regex")?;
+
+        let restarted = restart::relaunch_node(
+            &self.builder.nodes_dir,
+            &mut self.manifest,
+            record,
+            &[],
+            STOP_TIMEOUT,
+            &join_regex,
+            READY_TIMEOUT,
+        );
+        // The node was stopped by now, unless that failed
+        self.reap();
+        let child = restarted?;
+        let _ = self.children.insert(name, child);
+        Ok(())
+    }
+
+    /// Stop all the nodes of the network, reporting how each of them was brought down.
+    pub fn shutdown(mut self) -> Result<Vec<StoppedNode>> {
+        let stopped = self.stop()?;
+        self.shut_down = true;
+        Ok(stopped)
+    }
+
+    /// Stop all the nodes of the network, reaping those launched through this handle.
+    fn stop(&mut self) -> Result<Vec<StoppedNode>> {
+        self.reap();
+        let stopped = stop_network(&self.builder.nodes_dir, STOP_TIMEOUT);
+        self.reap();
+        stopped
+    }
+
+    /// Reap the nodes launched through this handle which have exited, so they don't linger as
+    /// zombies of the embedding process.
+    fn reap(&mut self) {
+        self.children
+            .retain(|_, child| !matches!(child.try_wait(), Ok(Some(_))));
+    }
+
+    /// Record of the node named `name`, failing unless it's running.
+    fn running_node(&self, name: &str) -> Result<&NodeRecord> {
+        let name = parse_node_name(name)?;
+        let node = self
+            .manifest
+            .node(&name)
+            .ok_or_else(|| eyre!("There is no {} in the network", name))?;
+        if !process::is_node_running(node.pid, &node.root_dir) {
+            return Err(eyre!("{} (pid {}) is not running", node.name, node.pid));
+        }
+        Ok(node)
+    }
+
+    /// Pick up the changes made to the manifest of the network.
+    fn reload(&mut self) -> Result<()> {
+        self.manifest = NetworkManifest::load(&self.builder.nodes_dir)?;
+        Ok(())
+    }
+}
+
+impl Drop for Network {
+    /// Stop the nodes unless the network was already shut down, so they don't outlive e.g. a
+    /// failed test.
+    fn drop(&mut self) {
+        if self.shut_down {
+            return;
+        }
+        if let Err(error) = self.stop() {
+            warn!("Failed to shut down the network: {:?}", error);
+        }
+    }
+}
+
+impl Node {
+    /// Whether the node is still running
+    pub fn is_running(&self) -> bool {
+        process::is_node_running(self.pid, &self.root_dir)
+    }
+}
+
+impl From<&NodeRecord> for Node {
+    fn from(record: &NodeRecord) -> Self {
+        Self {
+            name: record.name.clone(),
+            pid: record.pid,
+            root_dir: record.root_dir.clone(),
+            version: record.node_version.clone(),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    /// Stub of sn_node, which only writes the connection info when run as the genesis node, logs
+    /// having joined the network and then idles
+    const STUB_NODE: &str = r#"#!/bin/sh
+if [ "$1" = "-V" ]; then echo "sn_node 0.1.0"; exit 0; fi
+root=""; first=0
+while [ $# -gt 0 ]; do
+    case "$1" in --root-dir) root="$2"; shift;; --first) first=1;; esac
+    shift
+done
+mkdir -p "$root"
+if [ $first = 1 ]; then
+    mkdir -p "$HOME/.safe/node"
+    echo '["stub-genesis-key", ["127.0.0.1:12000"]]' > "$HOME/.safe/node/node_connection_info.config"
+fi
+echo "$STUB_GREETING" > "$root/sn_node.log"
+while true; do sleep 1; done
+"#;
+
+    #[test]
+    fn launched_networks_can_be_grown_killed_from_and_shut_down() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let node_path = dir.path().join("sn_node");
+        fs::write(&node_path, STUB_NODE)?;
+        fs::set_permissions(&node_path, fs::Permissions::from_mode(0o755))?;
+
+        let mut network = NetworkBuilder::new()
+            .num_nodes(3)
+            .node_path(&node_path)
+            .nodes_dir(dir.path().join("nodes"))
+            .interval(Duration::from_millis(10))
+            .env("STUB_GREETING", "Joined the network")
+            .launch()?;
+        assert_eq!(network.genesis_key(), "stub-genesis-key");
+        assert_eq!(network.contacts(), ["127.0.0.1:12000".parse()?]);
+        assert_eq!(network.nodes_dir(), dir.path().join("nodes"));
+        let names: Vec<String> = network.nodes().into_iter().map(|node| node.name).collect();
+        assert_eq!(names, ["sn-node-genesis", "sn-node-2", "sn-node-3"]);
+        assert!(network.nodes().iter().all(Node::is_running));
+        let log = fs::read_to_string(network.root_dirs()[1].join("sn_node.log"))?;
+        assert_eq!(log.trim(), "Joined the network");
+
+        let added = network.add_nodes(2)?;
+        let names: Vec<&str> = added.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["sn-node-4", "sn-node-5"]);
+        assert!(added.iter().all(Node::is_running));
+
+        let killed = added[0].pid;
+        network.kill_node("4")?;
+        // Reaped rather than left a zombie of the test process
+        assert!(process::start_time(killed).is_none());
+        let running: Vec<String> = network
+            .nodes()
+            .into_iter()
+            .filter(Node::is_running)
+            .map(|node| node.name)
+            .collect();
+        assert_eq!(
+            running,
+            ["sn-node-genesis", "sn-node-2", "sn-node-3", "sn-node-5"]
+        );
+        assert!(network.kill_node("4").is_err());
+
+        let nodes = network.nodes();
+        let stopped = network.shutdown()?;
+        assert_eq!(stopped.len(), 5);
+        assert!(nodes.iter().all(|node| !node.is_running()));
+        assert!(nodes
+            .iter()
+            .all(|node| process::start_time(node.pid).is_none()));
+        Ok(())
+    }
+}
@@ -0,0 +1,316 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use structopt::StructOpt;
+use tracing::trace;
+
+use crate::{
+    manifest::NetworkManifest, node_index, spec::NodeSelector, supervisor, NetworkArgs,
+    GENESIS_NODE_NAME,
+};
+
+/// Name of the file in the nodes dir the proxies read the partition of the network from
+const PARTITION_FILENAME: &str = "partition.json";
+
+/// Split a network launched with `--proxy` into groups of nodes which can't reach each other
+///
+/// The proxies in front of the nodes drop all the packets between nodes of different groups,
+/// until the network is healed with `heal`. Nodes left out of all the groups can still reach any
+/// node.
+#[derive(Debug, StructOpt)]
+pub struct Partition {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Groups to split the network into, given as `<NAME>=<NODES>,...` where nodes are given by
+    /// number (the genesis node being #1), range of numbers or name pattern, e.g.
+    /// `--group a=1-5 --group b=6-15` or `--group b='sn-node-[6-15]'`
+    #[structopt(long = "group", number_of_values = 1, required = true)]
+    groups: Vec<Group>,
+}
+
+/// Heal the partition of a network made with `partition`, so all its nodes can reach each other
+#[derive(Debug, StructOpt)]
+pub struct Heal {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+}
+
+/// Named group of nodes given to `partition`.
+#[derive(Debug)]
+struct Group {
+    name: String,
+    select: Vec<GroupNodes>,
+}
+
+/// Nodes of a group, given by a range of numbers or as in a spec file
+#[derive(Debug)]
+enum GroupNodes {
+    /// Numbers of the nodes, the genesis node being #1
+    Range(RangeInclusive<usize>),
+    Select(NodeSelector),
+}
+
+impl FromStr for Group {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (name, nodes) = value
+            .split_once('=')
+            .ok_or_else(|| eyre!("Expected <NAME>=<NODES>,..., got '{}'", value))?;
+        let mut select = vec![];
+        for nodes in nodes.split(',').map(str::trim) {
+            // A range of node numbers, e.g. `6-15`
+            let range = nodes.split_once('-').and_then(|(start, end)| {
+                Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?))
+            });
+            match range {
+                Some((0, _)) => {
+                    return Err(eyre!(
+                        "Invalid range {} in '{}', nodes are numbered from 1",
+                        nodes,
+                        value
+                    ))
+                }
+                Some((start, end)) if start > end => {
+                    return Err(eyre!(
+                        "Invalid range {} in '{}', it starts after it ends",
+                        nodes,
+                        value
+                    ))
+                }
+                Some((start, end)) => select.push(GroupNodes::Range(start..=end)),
+                None if nodes == "genesis" => {
+                    select.push(GroupNodes::Select(NodeSelector::Index(1)))
+                }
+                None => select.push(GroupNodes::Select(nodes.parse()?)),
+            }
+        }
+        Ok(Self {
+            name: name.trim().to_string(),
+            select,
+        })
+    }
+}
+
+impl GroupNodes {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Range(range) if name == GENESIS_NODE_NAME => range.contains(&1),
+            Self::Range(range) => node_index(name).filter(|idx| range.contains(idx)).is_some(),
+            Self::Select(select) => select.matches(name),
+        }
+    }
+}
+
+/// Partition of a network, as enforced by its proxies.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct NetworkPartition {
+    /// Names of the nodes in each group
+    pub(crate) groups: BTreeMap<String, Vec<String>>,
+}
+
+impl Partition {
+    /// Partition the network with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let nodes_dir = self.network.nodes_dir()?;
+        let manifest = NetworkManifest::load(&nodes_dir)?;
+        ensure_proxied(&manifest)?;
+        if self.groups.len() < 2 {
+            return Err(eyre!(
+                "At least two groups are needed to partition the network"
+            ));
+        }
+
+        let mut partition = NetworkPartition::default();
+        let mut group_of: HashMap<&str, &str> = HashMap::new();
+        for group in &self.groups {
+            if partition.groups.contains_key(&group.name) {
+                return Err(eyre!("Group {} given more than once", group.name));
+            }
+
+            let mut names = vec![];
+            for node in &manifest.nodes {
+                let mut selected = false;
+                for select in &group.select {
+                    selected |= select.matches(&node.name);
+                }
+                if !selected {
+                    continue;
+                }
+                if let Some(other) = group_of.insert(&node.name, &group.name) {
+                    return Err(eyre!(
+                        "{} can't be in both group {} and group {}",
+                        node.name,
+                        other,
+                        group.name
+                    ));
+                }
+                names.push(node.name.clone());
+            }
+
+            if names.is_empty() {
+                return Err(eyre!("Group {} has no nodes of the network", group.name));
+            }
+            let _ = partition.groups.insert(group.name.clone(), names);
+        }
+
+        partition.save(&nodes_dir)?;
+
+        for node in &manifest.nodes {
+            let group = group_of.get(node.name.as_str()).copied();
+            println!("{:<20} {}", node.name, group.unwrap_or("(reaches all)"));
+        }
+        Ok(())
+    }
+}
+
+impl Heal {
+    /// Heal the network with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let nodes_dir = self.network.nodes_dir()?;
+        let manifest = NetworkManifest::load(&nodes_dir)?;
+        ensure_proxied(&manifest)?;
+
+        if NetworkPartition::remove(&nodes_dir)? {
+            println!("Healed the network partition");
+        } else {
+            println!("The network isn't partitioned");
+        }
+        Ok(())
+    }
+}
+
+/// Fail unless the traffic between the nodes of the network of `manifest` is relayed by proxies,
+/// which are the ones partitioning it.
+fn ensure_proxied(manifest: &NetworkManifest) -> Result<()> {
+    if manifest.proxied && supervisor::running_supervisor(manifest).is_some() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "The network isn't running behind proxies, launch it with --proxy to partition it"
+        ))
+    }
+}
+
+impl NetworkPartition {
+    pub(crate) fn path(nodes_dir: &Path) -> PathBuf {
+        nodes_dir.join(PARTITION_FILENAME)
+    }
+
+    /// Load the partition from `nodes_dir`, if the network is partitioned.
+    pub(crate) fn load(nodes_dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(nodes_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = File::open(&path).wrap_err_with(|| {
+            format!("Failed to open network partition at '{}'", path.display())
+        })?;
+        let partition = serde_json::from_reader(BufReader::new(file)).wrap_err_with(|| {
+            format!("Failed to parse network partition at '{}'", path.display())
+        })?;
+        Ok(Some(partition))
+    }
+
+    /// Write the partition into `nodes_dir`, replacing any previous one.
+    fn save(&self, nodes_dir: &Path) -> Result<()> {
+        let path = Self::path(nodes_dir);
+        // The proxies poll the file, so they must never see it half-written
+        let tmp_path = path.with_extension("json.tmp");
+        let file = File::create(&tmp_path).wrap_err_with(|| {
+            format!(
+                "Failed to create network partition at '{}'",
+                tmp_path.display()
+            )
+        })?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)
+            .wrap_err("Failed to serialise network partition")?;
+        writer.flush().wrap_err_with(|| {
+            format!(
+                "Failed to write network partition at '{}'",
+                tmp_path.display()
+            )
+        })?;
+        fs::rename(&tmp_path, &path).wrap_err_with(|| {
+            format!("Failed to write network partition at '{}'", path.display())
+        })?;
+
+        trace!("Network partition written to {}", path.display());
+        Ok(())
+    }
+
+    /// Remove the partition from `nodes_dir`, returning whether the network was partitioned.
+    pub(crate) fn remove(nodes_dir: &Path) -> Result<bool> {
+        let path = Self::path(nodes_dir);
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(&path).wrap_err_with(|| {
+            format!("Failed to remove network partition at '{}'", path.display())
+        })?;
+        Ok(true)
+    }
+
+    /// Group of each of the nodes in the partition
+    pub(crate) fn group_of(&self) -> HashMap<String, String> {
+        self.groups
+            .iter()
+            .flat_map(|(group, names)| names.iter().map(move |name| (name.clone(), group.clone())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group_selects(group: &str, name: &str) -> Result<bool> {
+        let group: Group = group.parse()?;
+        Ok(group.select.iter().any(|select| select.matches(name)))
+    }
+
+    #[test]
+    fn groups_select_nodes_by_number_range_or_pattern() -> Result<()> {
+        for (group, name, selected) in [
+            ("a=1-5", GENESIS_NODE_NAME, true),
+            ("a=1-5", "sn-node-5", true),
+            ("a=1-5", "sn-node-6", false),
+            ("a=2-1000000000", "sn-node-999", true),
+            ("a=genesis", GENESIS_NODE_NAME, true),
+            ("a=genesis,7", "sn-node-7", true),
+            ("a=sn-node-[6-15]", "sn-node-15", true),
+            ("a=sn-node-[6-15]", "sn-node-5", false),
+        ] {
+            assert_eq!(group_selects(group, name)?, selected, "{} {}", group, name);
+        }
+
+        let group: Group = " a = 2-3 ".parse()?;
+        assert_eq!(group.name, "a");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_groups_are_rejected() {
+        for group in ["a", "a=5-2", "a=0-3", "a=sn-node-[5-2]"] {
+            assert!(group.parse::<Group>().is_err(), "{}", group);
+        }
+    }
+}
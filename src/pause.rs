@@ -0,0 +1,207 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+use tracing::{info, warn};
+
+use crate::{
+    manifest::{NetworkManifest, NodeRecord},
+    parse_node_name,
+    process::{self, Signal},
+    supervisor, NetworkArgs,
+};
+
+/// How often `freeze` checks whether it's been interrupted
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Pause nodes of a network started with `launch` (with SIGSTOP), until they're resumed
+///
+/// Paused nodes are still alive but don't respond to anything, like stalled peers.
+#[derive(Debug, StructOpt)]
+pub struct Pause {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Nodes to pause, e.g. "sn-node-4", "4" or "genesis"
+    #[structopt(parse(try_from_str = parse_node_name), required = true)]
+    nodes: Vec<String>,
+}
+
+/// Resume nodes paused with `pause` (with SIGCONT)
+#[derive(Debug, StructOpt)]
+pub struct Resume {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Nodes to resume, e.g. "sn-node-4", "4" or "genesis"
+    #[structopt(parse(try_from_str = parse_node_name), required = true)]
+    nodes: Vec<String>,
+}
+
+/// Pause nodes of a network started with `launch` for a while, resuming them afterwards
+///
+/// The nodes are resumed early if this is interrupted (e.g. with Ctrl-C).
+#[derive(Debug, StructOpt)]
+pub struct Freeze {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Nodes to freeze, e.g. "sn-node-4", "4" or "genesis"
+    #[structopt(parse(try_from_str = parse_node_name), required = true)]
+    nodes: Vec<String>,
+
+    /// How long to keep the nodes paused for, e.g. "30s"
+    #[structopt(long = "for", parse(try_from_str = humantime::parse_duration))]
+    duration: Duration,
+}
+
+impl Pause {
+    /// Pause the nodes with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let manifest = NetworkManifest::load(&self.network.nodes_dir()?)?;
+        let nodes = running_nodes(&manifest, &self.nodes)?;
+        pause_nodes(&nodes)
+    }
+}
+
+impl Resume {
+    /// Resume the nodes with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let manifest = NetworkManifest::load(&self.network.nodes_dir()?)?;
+        let nodes = running_nodes(&manifest, &self.nodes)?;
+        resume_nodes(&nodes)
+    }
+}
+
+impl Freeze {
+    /// Freeze the nodes with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let manifest = NetworkManifest::load(&self.network.nodes_dir()?)?;
+        let nodes = running_nodes(&manifest, &self.nodes)?;
+        supervisor::handle_shutdown_signals()?;
+
+        pause_nodes(&nodes)?;
+        info!(
+            "Freezing {} for {}",
+            self.nodes.join(", "),
+            humantime::format_duration(self.duration)
+        );
+
+        let deadline = Instant::now() + self.duration;
+        while Instant::now() < deadline && !supervisor::shutdown_requested() {
+            thread::sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+        }
+
+        resume_nodes(&nodes)
+    }
+}
+
+/// Records of the nodes named `names`, failing if any of them isn't running.
+fn running_nodes<'a>(
+    manifest: &'a NetworkManifest,
+    names: &[String],
+) -> Result<Vec<&'a NodeRecord>> {
+    names
+        .iter()
+        .map(|name| {
+            let node = manifest
+                .node(name)
+                .ok_or_else(|| eyre!("There is no {} in the network", name))?;
+            if !process::is_node_running(node.pid, &node.root_dir) {
+                return Err(eyre!("{} (pid {}) is not running", node.name, node.pid));
+            }
+            Ok(node)
+        })
+        .collect()
+}
+
+/// Send `signal` to each of `nodes`, reporting them as `done` once sent.
+fn signal_nodes(nodes: &[&NodeRecord], signal: Signal, done: &str) -> Result<()> {
+    for node in nodes {
+        process::signal_node(node.pid, signal)?;
+        println!("{:<20} {} (pid {})", node.name, done, node.pid);
+    }
+    Ok(())
+}
+
+/// Pause each of `nodes`, resuming those paused so far if one of them can't be paused, so they
+/// aren't left paused for good.
+fn pause_nodes(nodes: &[&NodeRecord]) -> Result<()> {
+    for (i, node) in nodes.iter().enumerate() {
+        if let Err(error) = signal_nodes(&[node], Signal::Stop, "paused") {
+            return Err(match resume_nodes(&nodes[..i]) {
+                Ok(()) => error,
+                Err(resume_error) => error.wrap_err(format!("{}, left paused", resume_error)),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Resume each of `nodes`, carrying on past any which can't be resumed so none of the others is
+/// left paused.
+fn resume_nodes(nodes: &[&NodeRecord]) -> Result<()> {
+    let mut failed = vec![];
+    for node in nodes {
+        if let Err(error) = signal_nodes(&[node], Signal::Continue, "resumed") {
+            warn!("{:?}", error);
+            failed.push(node.name.as_str());
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!("Failed to resume {}", failed.join(", ")))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{path::PathBuf, process::Command};
+
+    fn record(name: &str, pid: u32) -> NodeRecord {
+        NodeRecord {
+            name: name.to_string(),
+            pid,
+            root_dir: PathBuf::from("/tmp/nodes").join(name),
+            program: "sleep".to_string(),
+            args: vec![],
+            envs: vec![],
+            current_dir: None,
+            flame: false,
+            started_at: 1,
+            node_version: "sn_node 0.1.0".to_string(),
+            exit_status: None,
+        }
+    }
+
+    #[test]
+    fn nodes_paused_so_far_are_resumed_when_pausing_fails() -> Result<()> {
+        let mut child = Command::new("sleep").arg("30").spawn()?;
+        let paused = record("sn-node-2", child.id());
+        // No such process
+        let gone = record("sn-node-3", i32::MAX as u32);
+
+        let result = pause_nodes(&[&paused, &gone]);
+        let still_paused = process::is_paused(child.id());
+        child.kill()?;
+        let _ = child.wait()?;
+
+        assert!(result.is_err());
+        assert!(!still_paused);
+        Ok(())
+    }
+}
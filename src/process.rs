@@ -0,0 +1,287 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Helpers to inspect and signal node processes which are not (or no longer) our children.
+
+use eyre::{eyre, Result, WrapErr};
+use std::{
+    path::Path,
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Signals the launcher sends to node processes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Signal {
+    Interrupt,
+    Terminate,
+    Kill,
+    /// Pause the process, which then neither runs nor handles any signals but `Kill` until it's
+    /// continued
+    Stop,
+    Continue,
+}
+
+/// Check whether `pid` is a live process running the node rooted at `root_dir`.
+///
+/// PIDs get reused, so a process is only considered to be the node if its command line mentions
+/// the node's root dir.
+pub(crate) fn is_node_running(pid: u32, root_dir: &Path) -> bool {
+    is_running_with_arg(pid, &root_dir.to_string_lossy())
+}
+
+/// Check whether `pid` is the live launcher which recorded `start_time` (as given by
+/// [`start_time`]) when it started supervising.
+///
+/// PIDs get reused, and the launcher may run embedded in any program (e.g. a test binary), so
+/// the process is identified by when it started rather than by its command line.
+pub(crate) fn is_launcher_running(pid: u32, start_time: &str) -> bool {
+    self::start_time(pid).as_deref() == Some(start_time)
+}
+
+fn is_running_with_arg(pid: u32, arg: &str) -> bool {
+    command_line(pid)
+        .map(|cmdline| {
+            // Make sure e.g. `sn-node-1` doesn't match `sn-node-10`
+            cmdline.match_indices(arg).any(|(idx, _)| {
+                cmdline[idx + arg.len()..]
+                    .chars()
+                    .next()
+                    .filter(|next| !next.is_whitespace())
+                    .is_none()
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Check whether the node `pid` is paused, i.e. stopped by [`Signal::Stop`]. Nodes lead their own
+/// process group, which is what gets paused, so this checks all the processes of the group, e.g.
+/// the `sn_node` run by `cargo flamegraph` as well as cargo itself.
+#[cfg(unix)]
+pub(crate) fn is_paused(pid: u32) -> bool {
+    let output = match Command::new("ps")
+        .args(["-A", "-o", "pid=,pgid=,stat="])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return false,
+    };
+
+    String::from_utf8_lossy(&output.stdout).lines().any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            [process, group, stat, ..] => {
+                let pid = pid.to_string();
+                (process == pid || group == pid) && stat.starts_with('T')
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Processes can't be paused on Windows.
+#[cfg(windows)]
+pub(crate) fn is_paused(_pid: u32) -> bool {
+    false
+}
+
+/// Wait for the node to exit, returning `false` if it was still running after `timeout`.
+pub(crate) fn wait_for_exit(pid: u32, root_dir: &Path, timeout: Duration) -> bool {
+    let started = Instant::now();
+    while is_node_running(pid, root_dir) {
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    true
+}
+
+/// Make the process spawned by `cmd` the leader of its own process group, so it (and its own
+/// children) can be signalled independently of the launcher.
+#[cfg(unix)]
+pub(crate) fn own_process_group(cmd: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    let _ = cmd.process_group(0);
+}
+
+#[cfg(windows)]
+pub(crate) fn own_process_group(_cmd: &mut Command) {}
+
+/// When the process `pid` started, as reported by the OS, if it's running.
+#[cfg(unix)]
+pub(crate) fn start_time(pid: u32) -> Option<String> {
+    ps(pid, "lstart=")
+}
+
+/// When the process `pid` started, as reported by the OS, if it's running.
+#[cfg(windows)]
+pub(crate) fn start_time(pid: u32) -> Option<String> {
+    wmic(pid, "CreationDate")
+}
+
+#[cfg(unix)]
+fn command_line(pid: u32) -> Option<String> {
+    ps(pid, "args=")
+}
+
+/// Field `format` of the process `pid` as reported by `ps`, if it's running.
+#[cfg(unix)]
+fn ps(pid: u32, format: &str) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-o", format, "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(windows)]
+fn command_line(pid: u32) -> Option<String> {
+    wmic(pid, "CommandLine")
+}
+
+/// Property `property` of the process `pid` as reported by `wmic`, if it's running.
+#[cfg(windows)]
+fn wmic(pid: u32, property: &str) -> Option<String> {
+    let output = Command::new("wmic")
+        .args([
+            "process",
+            "where",
+            &format!("ProcessId={}", pid),
+            "get",
+            property,
+        ])
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Send `signal` to a node, along with anything the node spawned itself (e.g. `cargo flamegraph`
+/// runs `perf` which runs `sn_node`).
+#[cfg(unix)]
+pub(crate) fn signal_node(pid: u32, signal: Signal) -> Result<()> {
+    // Nodes lead their own process group, so signal the whole group, falling back to the process
+    // alone for nodes which weren't started that way.
+    // SAFETY: `kill` has no memory safety preconditions.
+    if unsafe { libc::kill(-(pid as libc::pid_t), signum(signal)) } == 0 {
+        return Ok(());
+    }
+
+    send_signal(pid, signal)
+}
+
+/// Send `signal` to the process `pid` alone.
+#[cfg(unix)]
+pub(crate) fn send_signal(pid: u32, signal: Signal) -> Result<()> {
+    // SAFETY: `kill` has no memory safety preconditions.
+    if unsafe { libc::kill(pid as libc::pid_t, signum(signal)) } != 0 {
+        return Err(eyre!(std::io::Error::last_os_error()))
+            .wrap_err_with(|| format!("Failed to send {:?} to process {}", signal, pid));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn signum(signal: Signal) -> libc::c_int {
+    match signal {
+        Signal::Interrupt => libc::SIGINT,
+        Signal::Terminate => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+        Signal::Stop => libc::SIGSTOP,
+        Signal::Continue => libc::SIGCONT,
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn signal_node(pid: u32, signal: Signal) -> Result<()> {
+    taskkill(pid, signal, true)
+}
+
+#[cfg(windows)]
+pub(crate) fn send_signal(pid: u32, signal: Signal) -> Result<()> {
+    taskkill(pid, signal, false)
+}
+
+#[cfg(windows)]
+fn taskkill(pid: u32, signal: Signal, tree: bool) -> Result<()> {
+    if matches!(signal, Signal::Stop | Signal::Continue) {
+        return Err(eyre!("Processes can't be paused or resumed on Windows"));
+    }
+
+    let pid = pid.to_string();
+    let mut args = vec!["/PID", pid.as_str()];
+    if tree {
+        args.push("/T");
+    }
+    if signal == Signal::Kill {
+        args.push("/F");
+    }
+
+    let status = Command::new("taskkill")
+        .args(&args)
+        .status()
+        .wrap_err_with(|| format!("Failed to send {:?} to process {}", signal, pid))?;
+    if !status.success() {
+        return Err(eyre!(
+            "Failed to send {:?} to process {} (status: {})",
+            signal,
+            pid,
+            status
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn launcher_is_identified_by_its_start_time() {
+        let pid = std::process::id();
+        let start_time = start_time(pid).expect("own start time");
+
+        assert!(is_launcher_running(pid, &start_time));
+        assert!(!is_launcher_running(pid, "Thu Jan  1 00:00:00 1970"));
+    }
+
+    #[test]
+    fn node_is_paused_if_any_process_of_its_group_is() -> Result<()> {
+        // As under `cargo flamegraph`, the paused process isn't the group leader
+        let mut cmd = Command::new("sh");
+        let _ = cmd.args(["-c", "sleep 30 & kill -STOP $!; wait"]);
+        own_process_group(&mut cmd);
+        let mut leader = cmd.spawn()?;
+        let pid = leader.id();
+
+        let started = Instant::now();
+        while !is_paused(pid) && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(POLL_INTERVAL);
+        }
+        let paused = is_paused(pid);
+        signal_node(pid, Signal::Kill)?;
+        let _ = leader.wait();
+
+        assert!(paused);
+        Ok(())
+    }
+}
@@ -0,0 +1,689 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Userspace UDP relays put in front of the nodes, to inject faults into the traffic between them
+//! without needing root or `tc`.
+//!
+//! Each node listens on a fixed local address, and advertises the address of its relay as its
+//! public one, so the other nodes reach it through the relay. The relay forwards the packets of
+//! each peer from a socket of its own, so the replies of the node go back through the relay too.
+
+use eyre::{eyre, Result, WrapErr};
+use rand::Rng;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fs, io,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+use crate::{manifest::NetworkManifest, partition::NetworkPartition, spec::LinkFaults};
+
+/// Largest UDP payload
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+/// Extra delay of the packets held back to be reordered, on top of their latency
+const REORDER_DELAY: Duration = Duration::from_millis(20);
+
+/// How often the relays check whether the network has been partitioned or healed
+const PARTITION_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long the relays wait for packets before checking whether they're to stop
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Pauses of a relay after failing to receive, doubled on each failure in a row
+const MIN_RECV_ERROR_BACKOFF: Duration = Duration::from_millis(1);
+const MAX_RECV_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Args the launcher sets on each node to put it behind its relay
+const PROXIED_ARGS: [&str; 2] = ["--local-addr", "--public-addr"];
+
+/// Relays of the nodes of a network, relaying once started and until dropped.
+pub(crate) struct Proxy {
+    endpoints: Vec<Endpoint>,
+    links: Arc<LinkTable>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// Addresses of a node and of its relay
+struct Endpoint {
+    name: String,
+    node_addr: SocketAddr,
+    /// Socket bound to `node_addr`, keeping it from being taken until the node is started
+    reservation: Mutex<Option<UdpSocket>>,
+    relay: UdpSocket,
+}
+
+/// Faults of each link, keyed by the sending node (`None` if it isn't one of the nodes) and the
+/// receiving node, along with the node listening on each address and the group of each node
+/// while the network is partitioned.
+struct LinkTable {
+    faults: HashMap<(Option<String>, String), Faults>,
+    names: HashMap<SocketAddr, String>,
+    partition: RwLock<HashMap<String, String>>,
+    /// Set once the proxy is dropped, for all its threads to stop
+    stopped: AtomicBool,
+}
+
+/// Faults injected into the packets of a link, resolved from the [`LinkFaults`] matching it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Faults {
+    latency: Duration,
+    jitter: Duration,
+    loss: f64,
+    duplicate: f64,
+    reorder: f64,
+}
+
+impl Proxy {
+    /// Bind a relay on `ip` for each of the nodes named `names`, and pick the address each node
+    /// listens on behind its relay. The faults of each link are those of `faults` matching it,
+    /// applied in order.
+    pub(crate) fn bind(ip: IpAddr, names: &[String], faults: &[LinkFaults]) -> Result<Self> {
+        let mut endpoints = vec![];
+        for name in names {
+            // The node's port stays bound until the node is about to bind it itself
+            let reservation = UdpSocket::bind((ip, 0))
+                .wrap_err_with(|| format!("Failed to pick a local address for {}", name))?;
+            let relay = UdpSocket::bind((ip, 0))
+                .wrap_err_with(|| format!("Failed to bind a relay for {}", name))?;
+            endpoints.push(Endpoint {
+                name: name.clone(),
+                node_addr: reservation.local_addr()?,
+                reservation: Mutex::new(Some(reservation)),
+                relay,
+            });
+        }
+
+        let mut links = LinkTable {
+            faults: HashMap::new(),
+            names: endpoints
+                .iter()
+                .map(|endpoint| (endpoint.node_addr, endpoint.name.clone()))
+                .collect(),
+            partition: RwLock::new(HashMap::new()),
+            stopped: AtomicBool::new(false),
+        };
+        for to in names {
+            let senders = names.iter().map(Some).chain(std::iter::once(None));
+            for from in senders {
+                let link_faults = Faults::resolve(faults, from.map(String::as_str), to);
+                if link_faults != Faults::default() {
+                    debug!(
+                        "Faults of {} -> {}: {:?}",
+                        from.map_or("*", |n| n),
+                        to,
+                        link_faults
+                    );
+                }
+                let _ = links
+                    .faults
+                    .insert((from.cloned(), to.clone()), link_faults);
+            }
+        }
+
+        Ok(Self {
+            endpoints,
+            links: Arc::new(links),
+            threads: vec![],
+        })
+    }
+
+    /// Args putting the node named `name` behind its relay.
+    pub(crate) fn node_args(&self, name: &str) -> Result<Vec<String>> {
+        let endpoint = self.endpoint(name)?;
+        Ok(vec![
+            PROXIED_ARGS[0].to_string(),
+            endpoint.node_addr.to_string(),
+            PROXIED_ARGS[1].to_string(),
+            endpoint.relay.local_addr()?.to_string(),
+        ])
+    }
+
+    /// Free the local address picked for the node named `name`, for it to bind it. To be called
+    /// right before starting the node.
+    pub(crate) fn release(&self, name: &str) -> Result<()> {
+        let endpoint = self.endpoint(name)?;
+        let mut reservation = match endpoint.reservation.lock() {
+            Ok(reservation) => reservation,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        drop(reservation.take());
+        Ok(())
+    }
+
+    /// `contacts` with the addresses of the nodes replaced by those of their relays.
+    pub(crate) fn relay_contacts(&self, contacts: &[SocketAddr]) -> Result<Vec<SocketAddr>> {
+        contacts
+            .iter()
+            .map(|contact| {
+                match self
+                    .endpoints
+                    .iter()
+                    .find(|endpoint| endpoint.node_addr == *contact)
+                {
+                    Some(endpoint) => Ok(endpoint.relay.local_addr()?),
+                    None => Ok(*contact),
+                }
+            })
+            .collect()
+    }
+
+    /// Start relaying the traffic of all the nodes, for as long as the launcher runs, following
+    /// the partitions of the network written into `nodes_dir`.
+    pub(crate) fn start(&mut self, nodes_dir: &Path) -> Result<()> {
+        // A partition left behind by a previous network doesn't apply to this one
+        let _ = NetworkPartition::remove(nodes_dir)?;
+        let links = Arc::clone(&self.links);
+        let nodes_dir = nodes_dir.to_path_buf();
+        let watcher = thread::Builder::new()
+            .name("partition-watcher".to_string())
+            .spawn(move || watch_partition(&links, &nodes_dir))
+            .wrap_err("Failed to start partition watcher thread")?;
+        self.threads.push(watcher);
+
+        for endpoint in &self.endpoints {
+            info!(
+                "Relaying {} at {} to {}",
+                endpoint.name,
+                endpoint.relay.local_addr()?,
+                endpoint.node_addr
+            );
+            let socket = endpoint.relay.try_clone()?;
+            socket.set_read_timeout(Some(STOP_POLL_INTERVAL))?;
+            let relay = Relay {
+                name: endpoint.name.clone(),
+                node_addr: endpoint.node_addr,
+                socket: Arc::new(socket),
+                links: Arc::clone(&self.links),
+                delivery: Delivery::start(),
+            };
+            let relay = thread::Builder::new()
+                .name(format!("relay-{}", relay.name))
+                .spawn(move || relay.run())
+                .wrap_err("Failed to start relay thread")?;
+            self.threads.push(relay);
+        }
+        Ok(())
+    }
+
+    fn endpoint(&self, name: &str) -> Result<&Endpoint> {
+        self.endpoints
+            .iter()
+            .find(|endpoint| endpoint.name == name)
+            .ok_or_else(|| eyre!("There is no relay for {}", name))
+    }
+}
+
+impl Drop for Proxy {
+    /// Stop relaying, waiting for all the relays to stop.
+    fn drop(&mut self) {
+        self.links.stopped.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Fail if any of the extra `args` to pass the nodes is one the proxies need to set themselves.
+pub(crate) fn ensure_not_proxied(args: &[String]) -> Result<()> {
+    for arg in args {
+        let name = arg.split('=').next().unwrap_or_default();
+        if PROXIED_ARGS.contains(&name) {
+            return Err(eyre!(
+                "Node arg '{}' can't be given along with --proxy, which sets it itself",
+                arg
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fail if the nodes of the network of `manifest` are behind relays. The relays only run as long
+/// as the launcher which launched the network, so its nodes can't be started again without them.
+pub(crate) fn ensure_not_relayed(manifest: &NetworkManifest) -> Result<()> {
+    if manifest.proxied {
+        return Err(eyre!(
+            "The nodes of this network were behind relays run by the launcher which launched it, they can't be started again without them"
+        ));
+    }
+    Ok(())
+}
+
+impl Faults {
+    /// Faults of the packets sent by `from` to `to`.
+    fn resolve(faults: &[LinkFaults], from: Option<&str>, to: &str) -> Self {
+        let mut resolved = Self::default();
+        for link in faults {
+            if !link.matches(from, to) {
+                continue;
+            }
+            if let Some(latency) = link.latency_msec {
+                resolved.latency = Duration::from_millis(latency);
+            }
+            if let Some(jitter) = link.jitter_msec {
+                resolved.jitter = Duration::from_millis(jitter);
+            }
+            if let Some(loss) = link.loss {
+                resolved.loss = loss / 100.0;
+            }
+            if let Some(duplicate) = link.duplicate {
+                resolved.duplicate = duplicate / 100.0;
+            }
+            if let Some(reorder) = link.reorder {
+                resolved.reorder = reorder / 100.0;
+            }
+        }
+        resolved
+    }
+
+    /// Delays after which to deliver a packet, none if it's lost and two if it's duplicated.
+    fn delays(&self) -> Vec<Duration> {
+        let mut rng = rand::thread_rng();
+        if rng.gen_bool(self.loss) {
+            return vec![];
+        }
+
+        let copies = if rng.gen_bool(self.duplicate) { 2 } else { 1 };
+        (0..copies)
+            .map(|_| {
+                let jitter = self.jitter.as_secs_f64() * rng.gen_range(-1.0..=1.0);
+                let mut delay =
+                    Duration::from_secs_f64((self.latency.as_secs_f64() + jitter).max(0.0));
+                // Holding a packet back lets the ones sent after it overtake it
+                if rng.gen_bool(self.reorder) {
+                    delay += REORDER_DELAY;
+                }
+                delay
+            })
+            .collect()
+    }
+}
+
+/// Relay in front of a node, forwarding the packets of each of its peers from a socket of its
+/// own, and the replies of the node back to the peer.
+struct Relay {
+    name: String,
+    node_addr: SocketAddr,
+    socket: Arc<UdpSocket>,
+    links: Arc<LinkTable>,
+    delivery: (Sender<Packet>, JoinHandle<()>),
+}
+
+/// Socket relaying the packets of a peer to the node, the faults of those packets and the name of
+/// the peer, if it's one of the nodes
+type Peer = (Arc<UdpSocket>, Faults, Option<String>);
+
+impl Relay {
+    fn run(self) {
+        let mut peers: HashMap<SocketAddr, Peer> = HashMap::new();
+        let mut replies = vec![];
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        let mut backoff = Duration::ZERO;
+        while !self.links.is_stopped() {
+            let (len, peer_addr) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(error) if is_timeout(&error) => continue,
+                Err(error) => {
+                    debug!("Relay of {} failed to receive: {}", self.name, error);
+                    back_off(&mut backoff);
+                    continue;
+                }
+            };
+            backoff = Duration::ZERO;
+
+            let (upstream, faults, peer) = match peers.get(&peer_addr) {
+                Some(peer) => peer.clone(),
+                None => match self.connect(peer_addr) {
+                    Ok((peer, thread)) => {
+                        replies.push(thread);
+                        let _ = peers.insert(peer_addr, peer.clone());
+                        peer
+                    }
+                    Err(error) => {
+                        debug!("{:?}", error);
+                        continue;
+                    }
+                },
+            };
+
+            if self.links.is_cut(peer.as_deref(), &self.name) {
+                continue;
+            }
+            schedule(&self.delivery.0, &faults, &upstream, None, &buf[..len]);
+        }
+
+        // The delivery thread stops once the relay and its reply threads are done with it
+        for thread in replies {
+            let _ = thread.join();
+        }
+        let (sender, delivery) = self.delivery;
+        drop(sender);
+        let _ = delivery.join();
+    }
+
+    /// Bind a socket relaying the packets of the peer at `peer_addr` to the node, and start
+    /// relaying the node's replies back, returning the socket, the faults of the packets sent to
+    /// the node and the name of the peer, if it's one of the nodes, along with the thread relaying
+    /// the replies.
+    fn connect(&self, peer_addr: SocketAddr) -> Result<(Peer, JoinHandle<()>)> {
+        let upstream = UdpSocket::bind((self.node_addr.ip(), 0))
+            .and_then(|socket| socket.connect(self.node_addr).map(|()| socket))
+            .and_then(|socket| {
+                socket
+                    .set_read_timeout(Some(STOP_POLL_INTERVAL))
+                    .map(|()| socket)
+            })
+            .wrap_err_with(|| format!("Failed to bind a relay socket for {}", peer_addr))?;
+        let upstream = Arc::new(upstream);
+
+        let peer = self.links.names.get(&peer_addr).cloned();
+        debug!(
+            "Relaying {} ({}) to {}",
+            peer.as_deref().unwrap_or("unknown peer"),
+            peer_addr,
+            self.name
+        );
+        let inbound = self.links.faults(peer.clone(), self.name.clone());
+        let outbound = peer
+            .clone()
+            .map(|peer| self.links.faults(Some(self.name.clone()), peer))
+            .unwrap_or_default();
+
+        let replies = Arc::clone(&upstream);
+        let links = Arc::clone(&self.links);
+        let name = self.name.clone();
+        let to = peer.clone();
+        let socket = Arc::clone(&self.socket);
+        let delivery = self.delivery.0.clone();
+        let thread = thread::Builder::new()
+            .name(format!("relay-{}-{}", self.name, peer_addr))
+            .spawn(move || {
+                let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+                let mut backoff = Duration::ZERO;
+                while !links.is_stopped() {
+                    let len = match replies.recv(&mut buf) {
+                        Ok(len) => len,
+                        Err(error) if is_timeout(&error) => continue,
+                        Err(error) => {
+                            debug!("Relay of {} failed to receive: {}", name, error);
+                            back_off(&mut backoff);
+                            continue;
+                        }
+                    };
+                    backoff = Duration::ZERO;
+
+                    if let Some(to) = &to {
+                        if links.is_cut(Some(&name), to) {
+                            continue;
+                        }
+                    }
+                    schedule(&delivery, &outbound, &socket, Some(peer_addr), &buf[..len]);
+                }
+            })
+            .wrap_err("Failed to start relay thread")?;
+
+        Ok(((upstream, inbound, peer), thread))
+    }
+}
+
+/// Whether `error` is a socket's read timeout expiring
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Pause after failing to receive, twice as long as after the previous failure in a row, so a
+/// socket which keeps failing doesn't spin.
+fn back_off(backoff: &mut Duration) {
+    *backoff = (*backoff * 2).clamp(MIN_RECV_ERROR_BACKOFF, MAX_RECV_ERROR_BACKOFF);
+    thread::sleep(*backoff);
+}
+
+impl LinkTable {
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    fn faults(&self, from: Option<String>, to: String) -> Faults {
+        self.faults.get(&(from, to)).copied().unwrap_or_default()
+    }
+
+    /// Whether the packets of `from` (`None` if it isn't one of the nodes) to `to` are dropped,
+    /// the two being in different groups of a partition.
+    fn is_cut(&self, from: Option<&str>, to: &str) -> bool {
+        let partition = match self.partition.read() {
+            Ok(partition) => partition,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match (from.and_then(|from| partition.get(from)), partition.get(to)) {
+            (Some(from_group), Some(to_group)) => from_group != to_group,
+            _ => false,
+        }
+    }
+
+    fn set_partition(&self, group_of: HashMap<String, String>) {
+        match self.partition.write() {
+            Ok(mut partition) => *partition = group_of,
+            Err(poisoned) => *poisoned.into_inner() = group_of,
+        }
+    }
+}
+
+/// Keep the partition of `links` in line with the one written into `nodes_dir`.
+fn watch_partition(links: &LinkTable, nodes_dir: &Path) {
+    let path = NetworkPartition::path(nodes_dir);
+    let mut last_modified = None;
+    while !links.is_stopped() {
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        if modified != last_modified {
+            last_modified = modified;
+            match NetworkPartition::load(nodes_dir) {
+                Ok(Some(partition)) => {
+                    info!("Partitioning the network: {:?}", partition.groups);
+                    links.set_partition(partition.group_of());
+                }
+                Ok(None) => {
+                    info!("Healing the network partition");
+                    links.set_partition(HashMap::new());
+                }
+                Err(error) => warn!("{:?}", error),
+            }
+        }
+        thread::sleep(PARTITION_POLL_INTERVAL);
+    }
+}
+
+/// Queue `data` to be sent on `socket` (to `dest`, unless it's connected) after the delays the
+/// link's `faults` give it.
+fn schedule(
+    delivery: &Sender<Packet>,
+    faults: &Faults,
+    socket: &Arc<UdpSocket>,
+    dest: Option<SocketAddr>,
+    data: &[u8],
+) {
+    let now = Instant::now();
+    for delay in faults.delays() {
+        let _ = delivery.send(Packet {
+            due: now + delay,
+            socket: Arc::clone(socket),
+            dest,
+            data: data.to_vec(),
+        });
+    }
+}
+
+/// Packet due to be sent by a relay
+struct Packet {
+    due: Instant,
+    socket: Arc<UdpSocket>,
+    dest: Option<SocketAddr>,
+    data: Vec<u8>,
+}
+
+/// Thread sending the packets queued by a relay once they're due.
+struct Delivery;
+
+impl Delivery {
+    fn start() -> (Sender<Packet>, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || Self::run(receiver));
+        (sender, thread)
+    }
+
+    fn run(receiver: Receiver<Packet>) {
+        // Packets are keyed by when they're due, and then by the order they were queued in
+        let mut queue: BinaryHeap<Reverse<(Instant, u64)>> = BinaryHeap::new();
+        let mut packets = HashMap::new();
+        let mut seq = 0u64;
+        loop {
+            let now = Instant::now();
+            while let Some(Reverse((due, key))) = queue.peek().copied() {
+                if due > now {
+                    break;
+                }
+                let _ = queue.pop();
+                if let Some(packet) = packets.remove(&key) {
+                    send(packet);
+                }
+            }
+
+            let received = match queue.peek() {
+                Some(Reverse((due, _))) => {
+                    receiver.recv_timeout(due.saturating_duration_since(now))
+                }
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(packet) => {
+                    queue.push(Reverse((packet.due, seq)));
+                    let _ = packets.insert(seq, packet);
+                    seq += 1;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+fn send(packet: Packet) {
+    let sent = match packet.dest {
+        Some(dest) => packet.socket.send_to(&packet.data, dest),
+        None => packet.socket.send(&packet.data),
+    };
+    if let Err(error) = sent {
+        debug!("Relay failed to send a packet: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_faults_override_earlier_ones() -> Result<()> {
+        let faults: Vec<LinkFaults> = [
+            "latency-msec=50,loss=5",
+            "2->*:latency-msec=200",
+            "*->3:loss=0",
+        ]
+        .iter()
+        .map(|faults| faults.parse())
+        .collect::<Result<_>>()?;
+        let resolve = |from, to| Faults::resolve(&faults, from, to);
+
+        let all = resolve(Some("sn-node-4"), "sn-node-5");
+        assert_eq!((all.latency, all.loss), (Duration::from_millis(50), 0.05));
+
+        let from_2 = resolve(Some("sn-node-2"), "sn-node-5");
+        assert_eq!(
+            (from_2.latency, from_2.loss),
+            (Duration::from_millis(200), 0.05)
+        );
+
+        let to_3 = resolve(Some("sn-node-2"), "sn-node-3");
+        assert_eq!((to_3.latency, to_3.loss), (Duration::from_millis(200), 0.0));
+
+        // Senders other than the nodes only match faults of links from any node
+        let unknown = resolve(None, "sn-node-5");
+        assert_eq!(unknown.latency, Duration::from_millis(50));
+        Ok(())
+    }
+
+    #[test]
+    fn relays_forward_packets_and_replies_until_dropped() -> Result<()> {
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let names = ["sn-node-2".to_string()];
+        let nodes_dir = tempfile::tempdir()?;
+        let mut proxy = Proxy::bind(ip, &names, &[])?;
+
+        let args = proxy.node_args(&names[0])?;
+        proxy.release(&names[0])?;
+        let node = UdpSocket::bind(&args[1])?;
+        proxy.start(nodes_dir.path())?;
+
+        let peer = UdpSocket::bind((ip, 0))?;
+        peer.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let _ = peer.send_to(b"ping", &args[3])?;
+
+        let mut buf = [0; 4];
+        node.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let (len, relayed_from) = node.recv_from(&mut buf)?;
+        assert_eq!(&buf[..len], b"ping");
+
+        let _ = node.send_to(b"pong", relayed_from)?;
+        let (len, from) = peer.recv_from(&mut buf)?;
+        assert_eq!(&buf[..len], b"pong");
+        assert_eq!(from.to_string(), args[3]);
+
+        // Dropping the proxy stops all its threads
+        drop(proxy);
+        Ok(())
+    }
+
+    #[test]
+    fn only_links_between_groups_are_cut() {
+        let links = LinkTable {
+            faults: HashMap::new(),
+            names: HashMap::new(),
+            partition: RwLock::new(HashMap::new()),
+            stopped: AtomicBool::new(false),
+        };
+        assert!(!links.is_cut(Some("sn-node-2"), "sn-node-3"));
+
+        let partition: NetworkPartition = serde_json::from_str(
+            r#"{"groups": {"a": ["sn-node-genesis", "sn-node-2"], "b": ["sn-node-3"]}}"#,
+        )
+        .expect("valid partition");
+        links.set_partition(partition.group_of());
+
+        assert!(links.is_cut(Some("sn-node-2"), "sn-node-3"));
+        assert!(links.is_cut(Some("sn-node-3"), "sn-node-genesis"));
+        assert!(!links.is_cut(Some("sn-node-genesis"), "sn-node-2"));
+        // Nodes left out of the groups, and other senders, reach any node
+        assert!(!links.is_cut(Some("sn-node-4"), "sn-node-3"));
+        assert!(!links.is_cut(None, "sn-node-3"));
+
+        links.set_partition(HashMap::new());
+        assert!(!links.is_cut(Some("sn-node-2"), "sn-node-3"));
+    }
+}
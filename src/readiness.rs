@@ -0,0 +1,166 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result, WrapErr};
+use regex::Regex;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Child,
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+use crate::logs::{self, LogCursor};
+
+/// Time a node is given to fail when it isn't otherwise probed for readiness
+pub(crate) const NODE_LIVENESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often readiness probes are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How a freshly spawned node is deemed ready, i.e. ready for the next node to be launched
+pub(crate) enum ReadinessProbe {
+    /// Wait a fixed amount of time
+    Delay(Duration),
+    /// Wait for a file (e.g. the genesis connection info) to be (re)written with valid JSON
+    File {
+        path: PathBuf,
+        previous: Option<Vec<u8>>,
+    },
+    /// Wait for a line matching the regex to be logged
+    LogLine { cursor: LogCursor, regex: Regex },
+}
+
+impl ReadinessProbe {
+    /// Probe for `path` being written, taking note of its current content so a stale file from a
+    /// previous run isn't mistaken for a new one. Must be created before the node is spawned.
+    pub(crate) fn file(path: &Path) -> Self {
+        Self::File {
+            path: path.to_path_buf(),
+            previous: fs::read(path).ok(),
+        }
+    }
+
+    /// Probe for a line matching `regex` being logged into `log_dir`. Must be created before the
+    /// node is spawned, as lines logged before that are ignored.
+    pub(crate) fn log_line(log_dir: &Path, regex: Regex) -> Result<Self> {
+        Ok(Self::LogLine {
+            cursor: LogCursor::from_end(log_dir)?,
+            regex,
+        })
+    }
+
+    /// Whether the node is ready, `elapsed` after it was spawned.
+    fn is_ready(&mut self, elapsed: Duration) -> Result<bool> {
+        match self {
+            Self::Delay(delay) => Ok(elapsed >= *delay),
+            Self::File { path, previous } => Ok(match fs::read(path) {
+                Ok(content) => {
+                    Some(&content) != previous.as_ref()
+                        && serde_json::from_slice::<serde_json::Value>(&content).is_ok()
+                }
+                Err(_) => false,
+            }),
+            Self::LogLine { cursor, regex } => Ok(cursor
+                .new_lines()?
+                .iter()
+                .any(|line| regex.is_match(&logs::line_message(line)))),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Delay(delay) => format!("{:?} to pass", delay),
+            Self::File { path, .. } => format!("'{}' to be written", path.display()),
+            Self::LogLine { regex, .. } => format!("a log line matching '{}'", regex),
+        }
+    }
+}
+
+/// Wait for the node to pass `probe`, failing if it exits or isn't ready within `timeout`.
+pub(crate) fn wait_until_ready(
+    node_name: &str,
+    child: &mut Child,
+    mut probe: ReadinessProbe,
+    timeout: Duration,
+) -> Result<()> {
+    debug!("Waiting for {} to be ready...", node_name);
+    let started = Instant::now();
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Err(eyre!("Node exited early (status: {})", status))
+                .wrap_err_with(|| format!("{} failed to start", node_name));
+        }
+
+        // Probe and check the timeout against the same point in time, so a delay probe as long
+        // as the timeout passes rather than times out
+        let elapsed = started.elapsed();
+        if probe.is_ready(elapsed)? {
+            debug!("{} ready after {:?}", node_name, elapsed);
+            return Ok(());
+        }
+
+        if elapsed >= timeout {
+            return Err(eyre!(
+                "{} wasn't ready within {:?}: timed out waiting for {}",
+                node_name,
+                timeout,
+                probe.describe()
+            ));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn sleeping_child() -> Result<Child> {
+        Ok(Command::new("sleep").arg("10").spawn()?)
+    }
+
+    #[test]
+    fn delay_as_long_as_the_timeout_passes() -> Result<()> {
+        let mut child = sleeping_child()?;
+        let delay = Duration::from_millis(250);
+        let result = wait_until_ready("sn-node-2", &mut child, ReadinessProbe::Delay(delay), delay);
+        child.kill()?;
+        let _ = child.wait();
+        result
+    }
+
+    #[test]
+    fn stale_file_isnt_taken_for_a_new_one() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("node_connection_info.config");
+        fs::write(&path, "[\"old\", []]")?;
+        let mut probe = ReadinessProbe::file(&path);
+        assert!(!probe.is_ready(Duration::ZERO)?);
+
+        fs::write(&path, "[\"new\", [")?;
+        assert!(!probe.is_ready(Duration::ZERO)?);
+        fs::write(&path, "[\"new\", []]")?;
+        assert!(probe.is_ready(Duration::ZERO)?);
+        Ok(())
+    }
+
+    #[test]
+    fn node_exiting_early_fails_the_probe() -> Result<()> {
+        let mut child = Command::new("true").spawn()?;
+        let probe = ReadinessProbe::Delay(Duration::from_secs(5));
+        assert!(wait_until_ready("sn-node-2", &mut child, probe, Duration::from_secs(5)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result, WrapErr};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::{fs, time::Duration};
+use structopt::StructOpt;
+use tracing::info;
+
+use crate::{
+    manifest::{NetworkManifest, NodeRecord},
+    node_index, parse_node_name,
+    stop::{self, StopOutcome},
+    supervisor, NetworkArgs, GENESIS_NODE_NAME,
+};
+
+/// Remove nodes from a network started with `launch`
+///
+/// The genesis node is never removed. Removed nodes are dropped from the network manifest, so
+/// they're no longer reported by `status`, and later launches with `--add` number new nodes after
+/// the remaining ones (and any node dirs left behind).
+#[derive(Debug, StructOpt)]
+pub struct Remove {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Nodes to remove, e.g. "sn-node-4" or "4"
+    #[structopt(
+        parse(try_from_str = parse_node_name),
+        required_unless_one = &["last", "random"],
+        conflicts_with_all = &["last", "random"]
+    )]
+    nodes: Vec<String>,
+
+    /// Remove this number of the highest numbered nodes
+    #[structopt(long, conflicts_with = "random")]
+    last: Option<usize>,
+
+    /// Remove this number of nodes picked at random
+    #[structopt(long)]
+    random: Option<usize>,
+
+    /// Seed to pick the nodes to remove at random with, to pick the same ones again. A random
+    /// seed is used (and printed) by default.
+    #[structopt(long, requires = "random")]
+    seed: Option<u64>,
+
+    /// Delete the directories (i.e. the data and logs) of the removed nodes too
+    #[structopt(long)]
+    delete_dirs: bool,
+
+    /// Time to wait for the nodes to exit gracefully before killing them, e.g. "10s"
+    #[structopt(long, default_value = "10s", parse(try_from_str = humantime::parse_duration))]
+    timeout: Duration,
+}
+
+impl Remove {
+    /// Remove the nodes selected by these arguments.
+    pub fn run(&self) -> Result<()> {
+        let nodes_dir = self.network.nodes_dir()?;
+        let mut manifest = NetworkManifest::load(&nodes_dir)?;
+        supervisor::ensure_not_supervised(&manifest)?;
+
+        let nodes = self.select(&manifest)?;
+        info!("Removing {}", nodes.join(", "));
+
+        let records: Vec<&NodeRecord> = nodes
+            .iter()
+            .filter_map(|name| manifest.node(name))
+            .collect();
+        let stopped = stop::stop_nodes(&records, self.timeout, true);
+        stop::print_summary(&stopped);
+
+        let mut still_running = vec![];
+        for node in stopped {
+            if node.outcome == StopOutcome::StillRunning {
+                still_running.push(node.name);
+                continue;
+            }
+
+            if let Some(record) = manifest.remove_node(&node.name) {
+                if self.delete_dirs && record.root_dir.exists() {
+                    fs::remove_dir_all(&record.root_dir).wrap_err_with(|| {
+                        format!("Failed to delete {}", record.root_dir.display())
+                    })?;
+                }
+            }
+        }
+        manifest.save(&nodes_dir)?;
+
+        if still_running.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "Couldn't stop {}, which were kept in the network",
+                still_running.join(", ")
+            ))
+        }
+    }
+
+    /// Names of the nodes to remove
+    fn select(&self, manifest: &NetworkManifest) -> Result<Vec<String>> {
+        if !self.nodes.is_empty() {
+            for name in &self.nodes {
+                if name == GENESIS_NODE_NAME {
+                    return Err(eyre!("The genesis node can't be removed"));
+                }
+                if manifest.node(name).is_none() {
+                    return Err(eyre!("There is no {} in the network", name));
+                }
+            }
+            return Ok(self.nodes.clone());
+        }
+
+        // Only numbered nodes can be removed, i.e. anything but the genesis node
+        let mut candidates: Vec<(usize, &str)> = manifest
+            .nodes
+            .iter()
+            .filter_map(|node| Some((node_index(&node.name)?, node.name.as_str())))
+            .collect();
+        candidates.sort_unstable();
+
+        let count = self.last.or(self.random).unwrap_or_default();
+        if count > candidates.len() {
+            return Err(eyre!(
+                "Can't remove {} nodes, the network has only {} besides the genesis node",
+                count,
+                candidates.len()
+            ));
+        }
+
+        let mut selected = if self.last.is_some() {
+            candidates.split_off(candidates.len() - count)
+        } else {
+            let seed = self.seed.unwrap_or_else(rand::random);
+            println!("Picking nodes at random with seed {}", seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            candidates
+                .choose_multiple(&mut rng, count)
+                .copied()
+                .collect()
+        };
+        selected.sort_unstable();
+
+        Ok(selected
+            .into_iter()
+            .map(|(_, name)| name.to_string())
+            .collect())
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result};
+use regex::Regex;
+use std::{path::Path, process::Child, time::Duration};
+use structopt::StructOpt;
+use tracing::{debug, info};
+
+use crate::{
+    cmd,
+    manifest::{unix_time_now, NetworkManifest, NodeRecord},
+    parse_node_name,
+    process::{self, Signal},
+    proxy,
+    readiness::{self, ReadinessProbe},
+    stop::{self, StopOutcome},
+    supervisor,
+    wait::DEFAULT_JOIN_REGEX,
+    NetworkArgs, GENESIS_NODE_NAME,
+};
+
+/// Restart a node of a network started with `launch`
+///
+/// The node is stopped if it's still running, and started again with the same arguments, env and
+/// root dir (and so contacts and genesis key) it was launched with. The genesis node can't be
+/// restarted, as started again it would found a new network rather than rejoin this one.
+#[derive(Debug, StructOpt)]
+pub struct Restart {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Node to restart, e.g. "sn-node-4" or "4", other than the genesis node
+    #[structopt(parse(try_from_str = parse_restarted_node_name))]
+    node: String,
+
+    /// Have the node clear the data left in its root dir by its previous run
+    #[structopt(long)]
+    clear_data: bool,
+
+    /// Time given to the node to rejoin the network, e.g. "60s"
+    #[structopt(long, default_value = "60s", parse(try_from_str = humantime::parse_duration))]
+    rejoin_timeout: Duration,
+
+    /// Time to wait for the node to exit gracefully before killing it, e.g. "10s"
+    #[structopt(long, default_value = "10s", parse(try_from_str = humantime::parse_duration))]
+    stop_timeout: Duration,
+
+    /// Regex matching the log line the node writes once it has rejoined the network
+    #[structopt(long, default_value = DEFAULT_JOIN_REGEX)]
+    join_regex: Regex,
+}
+
+impl Restart {
+    /// Restart the node with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let nodes_dir = self.network.nodes_dir()?;
+        let mut manifest = NetworkManifest::load(&nodes_dir)?;
+        supervisor::ensure_not_supervised(&manifest)?;
+
+        let record = manifest
+            .node(&self.node)
+            .cloned()
+            .ok_or_else(|| eyre!("There is no {} in the network", self.node))?;
+
+        // Only this run clears the data, so the recorded args are left as they were
+        let extra_args: &[&str] = if self.clear_data {
+            &["--clear-data"]
+        } else {
+            &[]
+        };
+        let _ = relaunch_node(
+            &nodes_dir,
+            &mut manifest,
+            record,
+            extra_args,
+            self.stop_timeout,
+            &self.join_regex,
+            self.rejoin_timeout,
+        )?;
+
+        if let Some(node) = manifest.node(&self.node) {
+            println!("{:<20} restarted (pid {})", node.name, node.pid);
+        }
+        Ok(())
+    }
+}
+
+/// Parse the name of a node to restart, as given to `restart`, rejecting the genesis node.
+fn parse_restarted_node_name(name: &str) -> Result<String> {
+    let name = parse_node_name(name)?;
+    ensure_not_genesis(&name)?;
+    Ok(name)
+}
+
+/// Fail if the node named `name` is the genesis node, which can only be started with `--first`,
+/// founding a new network rather than rejoining the one it founded.
+pub(crate) fn ensure_not_genesis(name: &str) -> Result<()> {
+    if name == GENESIS_NODE_NAME {
+        return Err(eyre!(
+            "{} can't be restarted, it would found a new network rather than rejoin this one",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Stop the node of `record` and start it again with `record`'s command line (plus `extra_args`
+/// for this run only), from the same root dir, waiting for it to log a line matching
+/// `rejoin_regex`. The manifest in `nodes_dir` is updated with the new process, or with the node
+/// being down if it doesn't rejoin. The node is left running if its binary can't be found.
+///
+/// Returns the process of the restarted node, left to the caller to reap.
+pub(crate) fn relaunch_node(
+    nodes_dir: &Path,
+    manifest: &mut NetworkManifest,
+    record: NodeRecord,
+    extra_args: &[&str],
+    stop_timeout: Duration,
+    rejoin_regex: &Regex,
+    rejoin_timeout: Duration,
+) -> Result<Child> {
+    ensure_not_genesis(&record.name)?;
+    proxy::ensure_not_relayed(manifest)?;
+    cmd::ensure_spawnable(&record)?;
+    let stopped = stop::stop_nodes(&[&record], stop_timeout, true);
+    if stopped
+        .iter()
+        .any(|node| node.outcome == StopOutcome::StillRunning)
+    {
+        return Err(eyre!(
+            "{} (pid {}) couldn't be stopped",
+            record.name,
+            record.pid
+        ));
+    }
+
+    start_recorded_node(
+        nodes_dir,
+        manifest,
+        record,
+        extra_args,
+        rejoin_regex,
+        rejoin_timeout,
+    )
+}
+
+/// Start a node with `record`'s command line (plus `extra_args` for this run only), waiting for it
+/// to log a line matching `join_regex`. The node's record in the manifest in `nodes_dir` is
+/// replaced (or added) with the new process, or with the node being down if it doesn't join.
+///
+/// Returns the process of the node, left to the caller to reap.
+pub(crate) fn start_recorded_node(
+    nodes_dir: &Path,
+    manifest: &mut NetworkManifest,
+    mut record: NodeRecord,
+    extra_args: &[&str],
+    join_regex: &Regex,
+    join_timeout: Duration,
+) -> Result<Child> {
+    ensure_not_genesis(&record.name)?;
+    proxy::ensure_not_relayed(manifest)?;
+    let name = record.name.clone();
+    info!("Starting {} with {}...", name, record.program);
+    let probe = ReadinessProbe::log_line(&record.root_dir, join_regex.clone())?;
+    let mut run = record.clone();
+    run.args
+        .extend(extra_args.iter().map(|arg| arg.to_string()));
+    let mut child = cmd::spawn_recorded(&run)?;
+    record.pid = child.id();
+    record.started_at = unix_time_now();
+    record.exit_status = None;
+
+    let ready = readiness::wait_until_ready(&name, &mut child, probe, join_timeout);
+    if ready.is_err() {
+        if let Err(error) = process::signal_node(child.id(), Signal::Kill) {
+            debug!("{:?}", error);
+        }
+        if let Ok(status) = child.wait() {
+            record.exit_status = Some(status.to_string());
+        }
+    }
+
+    match manifest.node_mut(&name) {
+        Some(node) => *node = record,
+        None => manifest.add_node(record),
+    }
+    manifest.save(nodes_dir)?;
+
+    ready.map(|()| child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_genesis_node_is_rejected_when_parsed() {
+        for node in ["genesis", "1", GENESIS_NODE_NAME] {
+            let restart = Restart::from_iter_safe(["restart", "-d", "nodes", node]);
+            assert!(restart.is_err(), "{}", node);
+        }
+
+        let restart = Restart::from_iter_safe(["restart", "-d", "nodes", "4"]);
+        assert_eq!(
+            restart.map(|restart| restart.node).ok().as_deref(),
+            Some("sn-node-4")
+        );
+    }
+}
@@ -0,0 +1,477 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Scenarios: scripted sequences of steps run against a local network, e.g. in CI.
+
+use eyre::{eyre, Result, WrapErr};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    fs, mem,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+use tracing::info;
+
+use crate::{
+    logs::LogCursor,
+    manifest::NetworkManifest,
+    parse_node_name,
+    process::{self, Signal},
+    supervisor, Cmd, Launch, NetworkArgs,
+};
+
+/// How often the node logs are checked, and a launch is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time given to a killed node to be gone
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Run the steps of a scenario file against a local network, reporting the outcome of each step
+///
+/// Scenario files are TOML, or YAML if their extension is `.yaml` or `.yml`, listing the steps as
+/// command lines, e.g. `steps = ["launch 11", "wait-joined 11", "kill sn-node-3", "sleep 10s"]`.
+/// Steps are the subcommands of this tool, run against the network in the nodes dir, along with:
+///
+/// - `launch <N> ...` / `add <N> ...`: launch a network of N nodes / add N nodes to it
+/// - `wait-joined <N> ...`: wait until N nodes have joined the network
+/// - `kill <NODE>...`: kill nodes outright
+/// - `sleep <DURATION>`: wait a while, e.g. `sleep 10s`
+/// - `assert-log-contains <NODE> <REGEX> [--within <DURATION>]`: check a node has logged a line
+///   matching the regex, waiting for it for up to the given time
+///
+/// The scenario stops at the first step which fails. Nodes run by a launch staying in the
+/// foreground (e.g. with `--proxy`) are shut down once the scenario is over.
+#[derive(Debug, StructOpt)]
+pub struct Scenario {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Path of the scenario file
+    path: PathBuf,
+}
+
+/// Contents of a scenario file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    /// Name of the scenario, reported along with its outcome
+    #[serde(default)]
+    name: Option<String>,
+    steps: Vec<String>,
+}
+
+/// A step of a scenario
+#[derive(Debug)]
+enum Step {
+    Builtin(BuiltinStep),
+    Cmd(Box<Cmd>),
+}
+
+/// Steps which aren't subcommands of their own
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum BuiltinStep {
+    /// Wait a while
+    Sleep {
+        #[structopt(parse(try_from_str = humantime::parse_duration))]
+        duration: Duration,
+    },
+    /// Kill nodes outright
+    Kill {
+        #[structopt(parse(try_from_str = parse_node_name), required = true)]
+        nodes: Vec<String>,
+    },
+    /// Check a node has logged a line matching a regex
+    AssertLogContains {
+        #[structopt(parse(try_from_str = parse_node_name))]
+        node: String,
+        regex: Regex,
+        /// Time to wait for the node to log such a line, e.g. "30s"
+        #[structopt(long, default_value = "0s", parse(try_from_str = humantime::parse_duration))]
+        within: Duration,
+    },
+}
+
+impl Scenario {
+    /// Run the scenario with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let scenario = ScenarioFile::load(&self.path)?;
+        let steps = scenario
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                self.parse_step(line)
+                    .wrap_err_with(|| format!("Invalid step {} '{}'", i + 1, line))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let name = scenario
+            .name
+            .clone()
+            .unwrap_or_else(|| self.path.display().to_string());
+        println!("Running scenario {}", name);
+        println!("{:<6} {:<8} {:<10} COMMAND", "STEP", "RESULT", "TIME");
+
+        let nodes_dir = self.network.nodes_dir()?;
+        let mut launchers = vec![];
+        let started = Instant::now();
+        for (i, (line, step)) in scenario.steps.iter().zip(steps).enumerate() {
+            info!("Step {}: {}", i + 1, line);
+            let step_started = Instant::now();
+            let result = supervisor::ensure_not_interrupted()
+                .and_then(|()| run_step(step, &nodes_dir, &mut launchers));
+            let elapsed = format_elapsed(step_started.elapsed());
+
+            if let Err(error) = result {
+                println!("{:<6} {:<8} {:<10} {}", i + 1, "FAILED", elapsed, line);
+                if let Err(error) = shut_down_launchers(launchers) {
+                    info!("{:?}", error);
+                }
+                return Err(error).wrap_err_with(|| {
+                    format!("Scenario {} failed at step {} '{}'", name, i + 1, line)
+                });
+            }
+            println!("{:<6} {:<8} {:<10} {}", i + 1, "ok", elapsed, line);
+        }
+
+        if let Err(error) = shut_down_launchers(launchers) {
+            info!("{:?}", error);
+        }
+        println!(
+            "Scenario {} passed: {} steps in {}",
+            name,
+            scenario.steps.len(),
+            format_elapsed(started.elapsed())
+        );
+        Ok(())
+    }
+
+    fn parse_step(&self, line: &str) -> Result<Step> {
+        let words = shell_words::split(line).wrap_err("Invalid quoting")?;
+        let (name, args) = words.split_first().ok_or_else(|| eyre!("Empty step"))?;
+
+        let mut cmd_line = vec![env!("CARGO_PKG_NAME").to_string()];
+        match name.as_str() {
+            "sleep" | "kill" | "assert-log-contains" => {
+                cmd_line.extend(words.iter().cloned());
+                return Ok(Step::Builtin(BuiltinStep::from_iter_safe(cmd_line)?));
+            }
+            "scenario" | "join" => {
+                return Err(eyre!("'{}' can't be run as a step of a scenario", name));
+            }
+            // `launch <N>`, `add <N>` and `wait-joined <N>` are shorthands for the equivalent
+            // subcommands with the number of nodes given as a flag
+            "launch" | "add" | "wait-joined" => {
+                let (subcommand, count_flag) = match name.as_str() {
+                    "wait-joined" => ("wait", "--nodes"),
+                    _ => ("launch", "--num-nodes"),
+                };
+                cmd_line.push(subcommand.to_string());
+                self.push_network_args(&mut cmd_line);
+                if name == "add" {
+                    cmd_line.push("--add".to_string());
+                }
+                match args.split_first() {
+                    Some((count, rest)) if count.parse::<usize>().is_ok() => {
+                        cmd_line.push(count_flag.to_string());
+                        cmd_line.push(count.clone());
+                        cmd_line.extend(rest.iter().cloned());
+                    }
+                    _ => cmd_line.extend(args.iter().cloned()),
+                }
+            }
+            _ => {
+                cmd_line.push(name.clone());
+                self.push_network_args(&mut cmd_line);
+                cmd_line.extend(args.iter().cloned());
+            }
+        }
+
+        Ok(Step::Cmd(Box::new(Cmd::from_iter_safe(cmd_line)?)))
+    }
+
+    /// Have the subcommand just pushed onto `cmd_line` run against the scenario's network. The
+    /// flags go right after the subcommand, as anything after a `--` in a step is passed to the
+    /// nodes.
+    fn push_network_args(&self, cmd_line: &mut Vec<String>) {
+        cmd_line.push("--nodes-dir".to_string());
+        cmd_line.push(self.network.nodes_dir.display().to_string());
+        if let Some(network_name) = &self.network.network_name {
+            cmd_line.push("--network-name".to_string());
+            cmd_line.push(network_name.clone());
+        }
+    }
+}
+
+impl ScenarioFile {
+    /// Read a scenario from `path`, which is parsed as YAML if its extension is `.yaml` or `.yml`
+    /// and as TOML otherwise.
+    fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read scenario {}", path.display()))?;
+
+        let yaml = path
+            .extension()
+            .filter(|ext| *ext == "yaml" || *ext == "yml")
+            .is_some();
+        let scenario = if yaml {
+            serde_yaml::from_str(&contents).map_err(eyre::Report::from)
+        } else {
+            toml::from_str(&contents).map_err(eyre::Report::from)
+        };
+        scenario.wrap_err_with(|| format!("Failed to parse scenario {}", path.display()))
+    }
+}
+
+fn run_step(
+    step: Step,
+    nodes_dir: &Path,
+    launchers: &mut Vec<JoinHandle<Result<()>>>,
+) -> Result<()> {
+    match step {
+        Step::Builtin(BuiltinStep::Sleep { duration }) => {
+            let deadline = Instant::now() + duration;
+            while Instant::now() < deadline {
+                supervisor::ensure_not_interrupted()?;
+                thread::sleep(
+                    POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())),
+                );
+            }
+            Ok(())
+        }
+        Step::Builtin(BuiltinStep::Kill { nodes }) => kill_nodes(nodes_dir, &nodes),
+        Step::Builtin(BuiltinStep::AssertLogContains {
+            node,
+            regex,
+            within,
+        }) => assert_log_contains(nodes_dir, &node, &regex, within),
+        Step::Cmd(cmd) => match *cmd {
+            Cmd::Launch(launch) => {
+                let launcher = start_launcher(launch, nodes_dir)?;
+                launchers.extend(launcher);
+                Ok(())
+            }
+            Cmd::Stop(stop) => {
+                // Launchers of this scenario still supervising the network shut its nodes down
+                // themselves, leaving `stop` the nodes of any other launch
+                shut_down_launchers(mem::take(launchers))?;
+                stop.run()
+            }
+            cmd => cmd.run(),
+        },
+    }
+}
+
+/// Run `launch`, returning the launcher if it stays in the foreground (e.g. to supervise the
+/// nodes) once they're all launched.
+fn start_launcher(launch: Launch, nodes_dir: &Path) -> Result<Option<JoinHandle<Result<()>>>> {
+    let launcher = thread::spawn(move || launch.run());
+    loop {
+        if launcher.is_finished() {
+            return join_launcher(launcher).map(|()| None);
+        }
+
+        // The launcher only records itself in the manifest once all the nodes are launched
+        let supervised = NetworkManifest::load(nodes_dir)
+            .ok()
+            .and_then(|manifest| manifest.supervisor_pid)
+            .filter(|pid| *pid == std::process::id())
+            .is_some();
+        if supervised {
+            return Ok(Some(launcher));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn join_launcher(launcher: JoinHandle<Result<()>>) -> Result<()> {
+    launcher
+        .join()
+        .map_err(|_| eyre!("The launcher panicked"))?
+}
+
+/// Shut down the nodes of the launchers still running in the foreground, waiting for all of them
+/// to be done and returning the first error any of them failed with.
+fn shut_down_launchers(launchers: Vec<JoinHandle<Result<()>>>) -> Result<()> {
+    if launchers.is_empty() {
+        return Ok(());
+    }
+    supervisor::request_shutdown();
+    let mut result = Ok(());
+    for launcher in launchers {
+        match join_launcher(launcher) {
+            Err(error) if result.is_ok() => result = Err(error),
+            Err(error) => info!("{:?}", error),
+            Ok(()) => {}
+        }
+    }
+    supervisor::clear_shutdown();
+    result
+}
+
+fn kill_nodes(nodes_dir: &Path, names: &[String]) -> Result<()> {
+    let manifest = NetworkManifest::load(nodes_dir)?;
+    for name in names {
+        let node = manifest
+            .node(name)
+            .ok_or_else(|| eyre!("There is no {} in the network", name))?;
+        if !process::is_node_running(node.pid, &node.root_dir) {
+            return Err(eyre!("{} (pid {}) is not running", node.name, node.pid));
+        }
+
+        process::signal_node(node.pid, Signal::Kill)?;
+        if !process::wait_for_exit(node.pid, &node.root_dir, KILL_TIMEOUT) {
+            return Err(eyre!(
+                "{} (pid {}) is still running after {:?}",
+                node.name,
+                node.pid,
+                KILL_TIMEOUT
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check whether the node named `name` has logged a line matching `regex`, waiting for it for up
+/// to `within`.
+fn assert_log_contains(
+    nodes_dir: &Path,
+    name: &str,
+    regex: &Regex,
+    within: Duration,
+) -> Result<()> {
+    let log_dir = match NetworkManifest::load(nodes_dir)?.node(name) {
+        Some(node) => node.root_dir.clone(),
+        None => nodes_dir.join(name),
+    };
+
+    let deadline = Instant::now() + within;
+    let mut cursor = LogCursor::from_start(&log_dir);
+    loop {
+        if cursor.new_lines()?.iter().any(|line| regex.is_match(line)) {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(eyre!(
+                "{} hasn't logged any line matching '{}' in {}",
+                name,
+                regex,
+                log_dir.display()
+            ));
+        }
+        supervisor::ensure_not_interrupted()?;
+        thread::sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+    }
+}
+
+/// `elapsed` rounded to tenths of a second, e.g. "12.3s"
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("{:.1}s", elapsed.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_step(line: &str) -> Result<Step> {
+        let scenario = Scenario::from_iter_safe(["scenario", "-d", "/tmp/nodes", "ci.toml"])?;
+        scenario.parse_step(line)
+    }
+
+    #[test]
+    fn shorthand_steps_expand_to_subcommands() -> Result<()> {
+        match parse_step("launch 11 --proxy")? {
+            Step::Cmd(cmd) => match *cmd {
+                Cmd::Launch(launch) => {
+                    assert_eq!(launch.num_nodes, Some(11));
+                    assert!(launch.proxy && !launch.add_nodes_to_existing_network);
+                    assert_eq!(launch.network.nodes_dir, PathBuf::from("/tmp/nodes"));
+                }
+                cmd => panic!("launch step parsed as {:?}", cmd),
+            },
+            step => panic!("launch step parsed as {:?}", step),
+        }
+
+        match parse_step("add 2")? {
+            Step::Cmd(cmd) => match *cmd {
+                Cmd::Launch(launch) => {
+                    assert_eq!(launch.num_nodes, Some(2));
+                    assert!(launch.add_nodes_to_existing_network);
+                }
+                cmd => panic!("add step parsed as {:?}", cmd),
+            },
+            step => panic!("add step parsed as {:?}", step),
+        }
+
+        match parse_step("launch 11 -- --max-capacity=5")? {
+            Step::Cmd(cmd) => match *cmd {
+                Cmd::Launch(launch) => {
+                    assert_eq!(launch.common.trailing_node_args, ["--max-capacity=5"]);
+                    assert_eq!(launch.network.nodes_dir, PathBuf::from("/tmp/nodes"));
+                }
+                cmd => panic!("launch step parsed as {:?}", cmd),
+            },
+            step => panic!("launch step parsed as {:?}", step),
+        }
+
+        assert!(
+            matches!(parse_step("wait-joined 11")?, Step::Cmd(cmd) if matches!(*cmd, Cmd::Wait(_)))
+        );
+        assert!(
+            matches!(parse_step("stop --timeout 5s")?, Step::Cmd(cmd) if matches!(*cmd, Cmd::Stop(_)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_steps_are_parsed() -> Result<()> {
+        assert!(matches!(
+            parse_step("sleep 10s")?,
+            Step::Builtin(BuiltinStep::Sleep { duration }) if duration == Duration::from_secs(10)
+        ));
+        match parse_step("kill 3 sn-node-4")? {
+            Step::Builtin(BuiltinStep::Kill { nodes }) => {
+                assert_eq!(nodes, ["sn-node-3", "sn-node-4"])
+            }
+            step => panic!("kill step parsed as {:?}", step),
+        }
+        match parse_step("assert-log-contains genesis 'Joined the network' --within 30s")? {
+            Step::Builtin(BuiltinStep::AssertLogContains {
+                node,
+                regex,
+                within,
+            }) => {
+                assert_eq!(node, crate::GENESIS_NODE_NAME);
+                assert_eq!(regex.as_str(), "Joined the network");
+                assert_eq!(within, Duration::from_secs(30));
+            }
+            step => panic!("assert-log-contains step parsed as {:?}", step),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_steps_are_rejected() {
+        for line in [
+            "",
+            "join --genesis-key abc",
+            "scenario other.toml",
+            "sleep",
+            "kill",
+            "launch 'unclosed",
+        ] {
+            assert!(parse_step(line).is_err(), "{:?}", line);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Building `sn_node` from a source checkout.
+
+use eyre::{eyre, Result, WrapErr};
+use serde_json::Value;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use tracing::{debug, info};
+
+/// Name of the binary target built from the source checkout
+const SN_NODE_BIN: &str = "sn_node";
+
+/// Args of the cargo commands building `sn_node` from the crate or workspace at `source_dir`,
+/// e.g. `cargo build` or `cargo flamegraph`.
+pub(crate) fn cargo_args(source_dir: &Path, profile: &str, features: &[String]) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec![
+        "--manifest-path".into(),
+        source_dir.join("Cargo.toml").into(),
+        "--profile".into(),
+        profile.into(),
+    ];
+    if !features.is_empty() {
+        args.push("--features".into());
+        args.push(features.join(",").into());
+    }
+    args
+}
+
+/// Build `sn_node` from the crate or workspace at `source_dir` with `cargo build`, returning the
+/// path of the executable cargo produced.
+pub(crate) fn build_node(source_dir: &Path, profile: &str, features: &[String]) -> Result<PathBuf> {
+    let mut cmd = Command::new("cargo");
+    cmd.arg("build")
+        .args(cargo_args(source_dir, profile, features))
+        .args(["--bin", SN_NODE_BIN])
+        // Diagnostics and progress are rendered on stderr, which is passed through so the build
+        // can be followed, leaving stdout to the JSON messages
+        .arg("--message-format=json-render-diagnostics")
+        .stderr(Stdio::inherit());
+
+    info!(
+        "Building {} from {} ({} profile)...",
+        SN_NODE_BIN,
+        source_dir.display(),
+        profile
+    );
+    debug!("Running {:?}", cmd);
+    let output = cmd
+        .output()
+        .wrap_err_with(|| format!("Failed to run {:?}", cmd))?;
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "Building {} from {} failed (status: {}), see cargo's output above",
+            SN_NODE_BIN,
+            source_dir.display(),
+            output.status
+        ));
+    }
+
+    // The last artifact reported for the binary is the one built, any earlier ones being stale
+    let executable = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| {
+            message["reason"] == "compiler-artifact" && message["target"]["name"] == SN_NODE_BIN
+        })
+        .filter_map(|message| message["executable"].as_str().map(PathBuf::from))
+        .next()
+        .ok_or_else(|| {
+            eyre!(
+                "Building {} from {} didn't produce a {} executable",
+                SN_NODE_BIN,
+                source_dir.display(),
+                SN_NODE_BIN
+            )
+        })?;
+
+    info!("Built {}", executable.display());
+    Ok(executable)
+}
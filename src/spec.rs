@@ -0,0 +1,852 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Declarative description of a network to launch, read from a TOML or JSON file.
+
+use eyre::{eyre, Result, WrapErr};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use tracing::debug;
+
+use crate::{
+    absolute_program,
+    cmd::{self, NodeCmd},
+    node_name, node_verbosity, DEFAULT_RUST_LOG, GENESIS_NODE_NAME,
+};
+
+pub(crate) const DEFAULT_NUM_NODES: usize = 15;
+
+pub(crate) const DEFAULT_INTERVAL_MSEC: u64 = 100;
+
+/// Env var giving the number of nodes to launch
+const NODE_COUNT_ENV: &str = "NODE_COUNT";
+
+/// Env var giving the path of the sn_node binary to run the nodes with
+const NODE_PATH_ENV: &str = "SN_NODE_PATH";
+
+/// Settings of a network to launch. Every setting is optional, falling back to the command line
+/// flags and then to the launcher's defaults.
+///
+/// Keys are named after the equivalent `launch` flags, e.g.:
+///
+/// ```toml
+/// num-nodes = 11
+/// interval = 500
+/// rust-log = "safe_network=trace"
+///
+/// [[nodes]]
+/// select = "sn-node-[5-9]"
+/// args = ["--max-capacity", "1000"]
+/// env = { RUST_LOG = "safe_network=trace" }
+///
+/// [[faults]]
+/// to = 1
+/// latency-msec = 200
+/// loss = 5.0
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct NetworkSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) num_nodes: Option<usize>,
+    /// Milliseconds between launching each of the nodes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) nodes_verbosity: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rust_log: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) idle_timeout_msec: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) keep_alive_interval_msec: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) node_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) json_logs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) local: Option<bool>,
+    /// Relay the traffic between the nodes through UDP proxies, to inject faults into it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) proxy: Option<bool>,
+    /// Extra args passed to all the nodes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) args: Vec<String>,
+    /// Shares of the nodes run with other binaries than `node-path`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) node_mix: Vec<NodeMix>,
+    /// Overrides of the above for some of the nodes, applied in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) nodes: Vec<NodeOverride>,
+    /// Faults injected into the traffic between the nodes by the proxies, applied in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) faults: Vec<LinkFaults>,
+}
+
+/// Settings layered on top of the shared ones for the nodes matching `select`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct NodeOverride {
+    pub(crate) select: NodeSelector,
+    /// Extra args passed to the nodes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) args: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) nodes_verbosity: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) node_path: Option<PathBuf>,
+    /// Extra env vars set for the nodes, e.g. RUST_LOG
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) env: BTreeMap<String, String>,
+}
+
+impl NodeOverride {
+    fn node_path(select: NodeSelector, node_path: PathBuf) -> Self {
+        Self {
+            select,
+            args: vec![],
+            nodes_verbosity: None,
+            node_path: Some(node_path),
+            env: BTreeMap::new(),
+        }
+    }
+}
+
+/// Parse a `<NODES>=<PATH>` override of the binary run by the selected nodes.
+pub(crate) fn parse_node_path_override(value: &str) -> Result<NodeOverride> {
+    let (select, path) = value
+        .split_once('=')
+        .ok_or_else(|| eyre!("Expected <NODES>=<PATH>, got '{}'", value))?;
+    Ok(NodeOverride::node_path(select.parse()?, path.into()))
+}
+
+/// Faults injected into the packets the nodes matching `from` send to the nodes matching `to`,
+/// either of which selects all the nodes when left out. Percentages are of the packets relayed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct LinkFaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) from: Option<NodeSelector>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) to: Option<NodeSelector>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) latency_msec: Option<u64>,
+    /// Milliseconds by which the latency randomly varies either way
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) jitter_msec: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) loss: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) duplicate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reorder: Option<f64>,
+}
+
+impl LinkFaults {
+    /// Whether these faults apply to the packets sent by the node named `from` (`None` if the
+    /// sender isn't one of the nodes) to the node named `to`.
+    pub(crate) fn matches(&self, from: Option<&str>, to: &str) -> bool {
+        let from_matches = match (&self.from, from) {
+            (None, _) => true,
+            (Some(select), Some(from)) => select.matches(from),
+            (Some(_), None) => false,
+        };
+        let to_matches = match &self.to {
+            Some(select) => select.matches(to),
+            None => true,
+        };
+        from_matches && to_matches
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (name, percent) in [
+            ("loss", self.loss),
+            ("duplicate", self.duplicate),
+            ("reorder", self.reorder),
+        ] {
+            if let Some(percent) = percent.filter(|percent| !(0.0..=100.0).contains(percent)) {
+                return Err(eyre!(
+                    "Invalid {} of {}%, it must be between 0 and 100%",
+                    name,
+                    percent
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LinkFaults {
+    type Err = eyre::Report;
+
+    /// Parse `[<FROM>-><TO>:]<FAULT>=<VALUE>,...` faults, e.g. `1->*:latency-msec=50,loss=5`.
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || {
+            eyre!(
+                "Expected [<FROM>-><TO>:]<FAULT>=<VALUE>,..., got '{}'",
+                value
+            )
+        };
+
+        let (link, faults) = match value.split_once(':') {
+            Some((link, faults)) => (Some(link), faults),
+            None => (None, value),
+        };
+        let mut parsed = Self::default();
+        if let Some(link) = link {
+            let (from, to) = link.split_once("->").ok_or_else(invalid)?;
+            parsed.from = Some(from.trim().parse()?);
+            parsed.to = Some(to.trim().parse()?);
+        }
+
+        for fault in faults.split(',') {
+            let (name, fault_value) = fault.split_once('=').ok_or_else(invalid)?;
+            let invalid_value = || eyre!("Invalid {} in '{}'", name, value);
+            let fault_value = fault_value.trim().trim_end_matches('%');
+            match name.trim() {
+                "latency-msec" => {
+                    parsed.latency_msec = Some(fault_value.parse().map_err(|_| invalid_value())?)
+                }
+                "jitter-msec" => {
+                    parsed.jitter_msec = Some(fault_value.parse().map_err(|_| invalid_value())?)
+                }
+                "loss" => parsed.loss = Some(fault_value.parse().map_err(|_| invalid_value())?),
+                "duplicate" => {
+                    parsed.duplicate = Some(fault_value.parse().map_err(|_| invalid_value())?)
+                }
+                "reorder" => {
+                    parsed.reorder = Some(fault_value.parse().map_err(|_| invalid_value())?)
+                }
+                name => return Err(eyre!("Unknown fault '{}' in '{}'", name, value)),
+            }
+        }
+
+        parsed.validate()?;
+        Ok(parsed)
+    }
+}
+
+/// Share of the nodes (other than the genesis node) to run with another binary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct NodeMix {
+    pub(crate) path: PathBuf,
+    pub(crate) percent: u8,
+}
+
+impl FromStr for NodeMix {
+    type Err = eyre::Report;
+
+    /// Parse a `<PATH>=<PERCENT>` share, e.g. `v2/sn_node=50%`.
+    fn from_str(value: &str) -> Result<Self> {
+        let (path, percent) = value
+            .rsplit_once('=')
+            .ok_or_else(|| eyre!("Expected <PATH>=<PERCENT>, got '{}'", value))?;
+        let percent = percent
+            .trim_end_matches('%')
+            .parse()
+            .wrap_err_with(|| format!("Invalid percentage in '{}'", value))?;
+        Ok(Self {
+            path: path.into(),
+            percent,
+        })
+    }
+}
+
+/// Nodes an override applies to.
+#[derive(Debug, Clone)]
+pub(crate) enum NodeSelector {
+    /// Number of the node, the genesis node being #1
+    Index(usize),
+    /// Pattern matching the names of the nodes
+    Pattern(NodePattern),
+}
+
+/// Pattern matching node names, where `*` matches anything and `[N-M]` any number from N to M,
+/// e.g. `sn-node-[5-9]`, compiled when parsed.
+#[derive(Debug, Clone)]
+pub(crate) struct NodePattern {
+    pattern: String,
+    regex: Regex,
+    /// Numbers matched by each of the captures of the regex
+    ranges: Vec<RangeInclusive<u64>>,
+}
+
+impl FromStr for NodeSelector {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.parse() {
+            Ok(idx) => Ok(Self::Index(idx)),
+            Err(_) => Ok(Self::Pattern(value.parse()?)),
+        }
+    }
+}
+
+impl NodeSelector {
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Index(1) => name == GENESIS_NODE_NAME,
+            Self::Index(idx) => name == node_name(*idx),
+            Self::Pattern(pattern) => pattern.matches(name),
+        }
+    }
+}
+
+impl Serialize for NodeSelector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Index(idx) => serializer.serialize_u64(*idx as u64),
+            Self::Pattern(pattern) => serializer.serialize_str(&pattern.pattern),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeSelector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Selector {
+            Index(usize),
+            Pattern(String),
+        }
+
+        match Selector::deserialize(deserializer)? {
+            Selector::Index(idx) => Ok(Self::Index(idx)),
+            Selector::Pattern(pattern) => pattern
+                .parse()
+                .map(Self::Pattern)
+                .map_err(|error: eyre::Report| de::Error::custom(error)),
+        }
+    }
+}
+
+impl FromStr for NodePattern {
+    type Err = eyre::Report;
+
+    /// Compile `pattern` into a regex matching the node names it selects, capturing the number
+    /// matched by each of its ranges.
+    fn from_str(pattern: &str) -> Result<Self> {
+        let invalid = || eyre!("Invalid node pattern '{}'", pattern);
+
+        let mut regex = String::from("^");
+        let mut ranges = vec![];
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' => regex.push_str(".*"),
+                '[' => {
+                    let range: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                    let (start, end) = range.split_once('-').unwrap_or((&range, &range));
+                    let start = start.trim().parse().map_err(|_| invalid())?;
+                    let end = end.trim().parse().map_err(|_| invalid())?;
+                    if start > end {
+                        return Err(invalid());
+                    }
+                    ranges.push(start..=end);
+                    regex.push_str(r"(\d+)");
+                }
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex: Regex::new(&regex).map_err(|_| invalid())?,
+            ranges,
+        })
+    }
+}
+
+impl NodePattern {
+    fn matches(&self, name: &str) -> bool {
+        let matches = self.regex.captures(name).filter(|captures| {
+            self.ranges.iter().enumerate().all(|(i, range)| {
+                captures[i + 1]
+                    .parse()
+                    .ok()
+                    .filter(|number| range.contains(number))
+                    .is_some()
+            })
+        });
+        matches.is_some()
+    }
+}
+
+/// Path of the sn_node binary given by the launcher's env var, if any
+pub(crate) fn env_node_path() -> Option<PathBuf> {
+    env::var_os(NODE_PATH_ENV).map(PathBuf::from)
+}
+
+impl NetworkSpec {
+    /// Read a spec from `path`, which is parsed as JSON if its extension is `.json` and as TOML
+    /// otherwise.
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read network spec {}", path.display()))?;
+
+        let spec = if path.extension().filter(|ext| *ext == "json").is_some() {
+            serde_json::from_str(&contents).map_err(eyre::Report::from)
+        } else {
+            toml::from_str(&contents).map_err(eyre::Report::from)
+        };
+        let spec: Self =
+            spec.wrap_err_with(|| format!("Failed to parse network spec {}", path.display()))?;
+
+        for faults in &spec.faults {
+            faults.validate()?;
+        }
+
+        Ok(spec)
+    }
+
+    /// The settings given by the launcher's env vars, as looked up with `var` (e.g. from the
+    /// process env), which override those of a spec file and are overridden by the command line
+    /// flags.
+    pub(crate) fn from_env<F>(var: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let num_nodes = match var(NODE_COUNT_ENV) {
+            Some(num_nodes) => Some(
+                num_nodes
+                    .to_str()
+                    .and_then(|num_nodes| num_nodes.parse().ok())
+                    .ok_or_else(|| {
+                        eyre!(
+                            "Invalid {} env var {:?}, expected a number of nodes",
+                            NODE_COUNT_ENV,
+                            num_nodes
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            num_nodes,
+            node_path: var(NODE_PATH_ENV).map(PathBuf::from),
+            ..Self::default()
+        })
+    }
+
+    /// Layer `overrides` on top of this spec, each setting given in `overrides` replacing ours.
+    pub(crate) fn merge(self, overrides: Self) -> Self {
+        Self {
+            num_nodes: overrides.num_nodes.or(self.num_nodes),
+            interval: overrides.interval.or(self.interval),
+            ip: overrides.ip.or(self.ip),
+            nodes_verbosity: overrides.nodes_verbosity.or(self.nodes_verbosity),
+            rust_log: overrides.rust_log.or(self.rust_log),
+            idle_timeout_msec: overrides.idle_timeout_msec.or(self.idle_timeout_msec),
+            keep_alive_interval_msec: overrides
+                .keep_alive_interval_msec
+                .or(self.keep_alive_interval_msec),
+            node_path: overrides.node_path.or(self.node_path),
+            json_logs: overrides.json_logs.or(self.json_logs),
+            local: overrides.local.or(self.local),
+            proxy: overrides.proxy.or(self.proxy),
+            args: self.args.into_iter().chain(overrides.args).collect(),
+            node_mix: if overrides.node_mix.is_empty() {
+                self.node_mix
+            } else {
+                overrides.node_mix
+            },
+            nodes: self.nodes.into_iter().chain(overrides.nodes).collect(),
+            faults: self.faults.into_iter().chain(overrides.faults).collect(),
+        }
+    }
+
+    /// This spec with the binaries the nodes run made absolute against the current dir, as they're
+    /// recorded in the manifest to run the nodes again from wherever the launcher is run.
+    pub(crate) fn with_absolute_node_paths(mut self) -> Result<Self> {
+        if let Some(node_path) = &mut self.node_path {
+            *node_path = absolute_program(node_path)?;
+        }
+        for mix in &mut self.node_mix {
+            mix.path = absolute_program(&mix.path)?;
+        }
+        for node in &mut self.nodes {
+            if let Some(node_path) = &mut node.node_path {
+                *node_path = absolute_program(node_path)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// This spec with the launcher's defaults filled in for any setting it doesn't give, i.e.
+    /// what a launch with it actually runs.
+    pub(crate) fn resolved(&self) -> Self {
+        Self {
+            num_nodes: Some(self.num_nodes()),
+            interval: Some(self.interval.unwrap_or(DEFAULT_INTERVAL_MSEC)),
+            nodes_verbosity: Some(self.nodes_verbosity()),
+            rust_log: Some(self.rust_log().into_owned()),
+            json_logs: Some(self.json_logs()),
+            local: Some(self.local()),
+            proxy: Some(self.proxy()),
+            ..self.clone()
+        }
+    }
+
+    /// Overrides running the nodes among `node_ids` with the binaries of the node mix, each one
+    /// taking its share of the nodes, spread evenly over them. Nodes left over by the mix run
+    /// `node-path`.
+    pub(crate) fn node_mix_overrides(&self, node_ids: &[usize]) -> Result<Vec<NodeOverride>> {
+        let total: u32 = self.node_mix.iter().map(|mix| mix.percent as u32).sum();
+        if total > 100 {
+            return Err(eyre!(
+                "The node mix adds up to {}% of the nodes, it can't exceed 100%",
+                total
+            ));
+        }
+
+        // Shares of the nodes run by `node-path` (`None`) and each of the binaries of the mix
+        let shares: Vec<(Option<&PathBuf>, usize)> = std::iter::once((None, 100 - total as usize))
+            .chain(
+                self.node_mix
+                    .iter()
+                    .map(|mix| (Some(&mix.path), mix.percent as usize)),
+            )
+            .collect();
+
+        let mut assigned = vec![0; shares.len()];
+        let mut overrides = vec![];
+        for (n, &idx) in node_ids.iter().enumerate() {
+            // Run this node with the binary furthest behind its share of the nodes so far
+            let behind = shares.iter().enumerate().min_by_key(|(i, (_, percent))| {
+                100 * assigned[*i] as isize - ((n + 1) * percent) as isize
+            });
+            if let Some((i, (path, _))) = behind {
+                assigned[i] += 1;
+                if let Some(path) = path {
+                    overrides.push(NodeOverride::node_path(
+                        NodeSelector::Index(idx),
+                        path.to_path_buf(),
+                    ));
+                }
+            }
+        }
+
+        Ok(overrides)
+    }
+
+    /// Command running the node named `name`, i.e. `node_cmd` with the overrides selecting that
+    /// node applied on top.
+    pub(crate) fn node_cmd<'a>(
+        &'a self,
+        name: &str,
+        node_cmd: &NodeCmd<'a>,
+    ) -> Result<NodeCmd<'a>> {
+        let mut cmd = node_cmd.clone();
+        for node in &self.nodes {
+            if !node.select.matches(name) {
+                continue;
+            }
+            debug!("Applying overrides to {}: {:?}", name, node);
+
+            if let Some(node_path) = &node.node_path {
+                cmd.set_path(node_path.as_path());
+            }
+            if let Some(nodes_verbosity) = node.nodes_verbosity {
+                cmd.set_verbosity(node_verbosity(nodes_verbosity));
+            }
+            cmd::ensure_not_managed(&node.args)?;
+            for arg in &node.args {
+                cmd.push_arg(arg.as_str());
+            }
+            for (key, value) in &node.env {
+                cmd.set_env(key.as_str(), value.as_str());
+            }
+        }
+
+        Ok(cmd)
+    }
+
+    pub(crate) fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).wrap_err("Failed to serialize network spec")
+    }
+
+    /// Number of nodes, including the genesis node
+    pub(crate) fn num_nodes(&self) -> usize {
+        self.num_nodes.unwrap_or(DEFAULT_NUM_NODES)
+    }
+
+    pub(crate) fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval.unwrap_or(DEFAULT_INTERVAL_MSEC))
+    }
+
+    pub(crate) fn nodes_verbosity(&self) -> u8 {
+        self.nodes_verbosity.unwrap_or_default()
+    }
+
+    /// RUST_LOG to run the nodes with, falling back to the launcher's own RUST_LOG env var.
+    pub(crate) fn rust_log(&self) -> Cow<'_, str> {
+        match self.rust_log.as_deref() {
+            Some(rust_log) => rust_log.into(),
+            None => match env::var("RUST_LOG") {
+                Ok(rust_log_env) => rust_log_env.into(),
+                Err(_) => DEFAULT_RUST_LOG.into(),
+            },
+        }
+    }
+
+    pub(crate) fn json_logs(&self) -> bool {
+        self.json_logs.unwrap_or_default()
+    }
+
+    pub(crate) fn local(&self) -> bool {
+        self.local.unwrap_or_default()
+    }
+
+    /// Whether to relay the traffic between the nodes through proxies, which is the case by
+    /// default when faults are to be injected into it.
+    pub(crate) fn proxy(&self) -> bool {
+        self.proxy.unwrap_or(!self.faults.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lookup of the env vars in `vars` rather than in the process env
+    fn vars(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<OsString> {
+        move |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.into())
+        }
+    }
+
+    #[test]
+    fn env_vars_give_the_node_count_and_path() -> Result<()> {
+        let spec = NetworkSpec::from_env(vars(&[
+            ("NODE_COUNT", "9"),
+            ("SN_NODE_PATH", "/v2/sn_node"),
+        ]))?;
+        assert_eq!(spec.num_nodes, Some(9));
+        assert_eq!(spec.node_path, Some(PathBuf::from("/v2/sn_node")));
+
+        let spec = NetworkSpec::from_env(vars(&[]))?;
+        assert!(spec.num_nodes.is_none() && spec.node_path.is_none());
+
+        assert!(NetworkSpec::from_env(vars(&[("NODE_COUNT", "many")])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn merged_settings_override_or_extend_ours() -> Result<()> {
+        let file: NetworkSpec = toml::from_str(
+            r#"
+            num-nodes = 11
+            interval = 500
+            json-logs = true
+            local = true
+            args = ["--max-capacity", "1000"]
+
+            [[nodes]]
+            select = 4
+            args = ["--clear-data"]
+            "#,
+        )?;
+        let flags = NetworkSpec {
+            num_nodes: Some(7),
+            json_logs: Some(false),
+            args: vec!["--skip-auto-port-forwarding".to_string()],
+            nodes: vec![NodeOverride::node_path(
+                NodeSelector::Index(5),
+                PathBuf::from("/v2/sn_node"),
+            )],
+            ..NetworkSpec::default()
+        };
+        let spec = file.merge(flags);
+
+        assert_eq!(spec.num_nodes(), 7);
+        assert_eq!(spec.interval(), Duration::from_millis(500));
+        assert!(!spec.json_logs());
+        assert!(spec.local());
+        assert_eq!(
+            spec.args,
+            ["--max-capacity", "1000", "--skip-auto-port-forwarding"]
+        );
+        assert_eq!(spec.nodes.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn resolved_spec_gives_the_defaults() {
+        let spec = NetworkSpec::default().resolved();
+
+        assert_eq!(spec.num_nodes, Some(DEFAULT_NUM_NODES));
+        assert_eq!(spec.interval, Some(DEFAULT_INTERVAL_MSEC));
+        assert_eq!(spec.json_logs, Some(false));
+        assert_eq!(spec.local, Some(false));
+        assert_eq!(spec.proxy, Some(false));
+    }
+
+    #[test]
+    fn faults_imply_a_proxy_unless_turned_off() -> Result<()> {
+        let mut spec = NetworkSpec {
+            faults: vec!["loss=5".parse()?],
+            ..NetworkSpec::default()
+        };
+        assert!(spec.proxy());
+
+        spec = spec.merge(NetworkSpec {
+            proxy: Some(false),
+            ..NetworkSpec::default()
+        });
+        assert!(!spec.proxy());
+        Ok(())
+    }
+
+    #[test]
+    fn selectors_match_node_numbers_and_patterns() -> Result<()> {
+        let genesis: NodeSelector = "1".parse()?;
+        assert!(genesis.matches(GENESIS_NODE_NAME));
+        assert!(!genesis.matches("sn-node-1"));
+
+        let fourth: NodeSelector = "4".parse()?;
+        assert!(fourth.matches("sn-node-4"));
+        assert!(!fourth.matches("sn-node-14"));
+
+        let range: NodeSelector = "sn-node-[5-9]".parse()?;
+        for (name, matches) in [
+            ("sn-node-4", false),
+            ("sn-node-5", true),
+            ("sn-node-9", true),
+            ("sn-node-10", false),
+            ("sn-node-5x", false),
+        ] {
+            assert_eq!(range.matches(name), matches, "{}", name);
+        }
+
+        let any: NodeSelector = "*-[2]".parse()?;
+        assert!(any.matches("sn-node-2"));
+        assert!(!any.matches(GENESIS_NODE_NAME));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        for pattern in ["sn-node-[9-5]", "sn-node-[a-b]", "sn-node-[5-"] {
+            assert!(pattern.parse::<NodeSelector>().is_err(), "{}", pattern);
+        }
+
+        let error = toml::from_str::<NetworkSpec>("[[nodes]]\nselect = \"sn-node-[9-5]\"")
+            .expect_err("invalid pattern in a spec file");
+        assert!(error.to_string().contains("Invalid node pattern"));
+    }
+
+    #[test]
+    fn selectors_round_trip_through_a_spec_file() -> Result<()> {
+        let spec = NetworkSpec {
+            nodes: vec![
+                NodeOverride::node_path("3".parse()?, PathBuf::from("/v2/sn_node")),
+                NodeOverride::node_path("sn-node-[5-9]".parse()?, PathBuf::from("/v2/sn_node")),
+            ],
+            ..NetworkSpec::default()
+        };
+        let spec: NetworkSpec = toml::from_str(&spec.to_toml()?)?;
+
+        assert!(matches!(spec.nodes[0].select, NodeSelector::Index(3)));
+        assert!(spec.nodes[1].select.matches("sn-node-7"));
+        Ok(())
+    }
+
+    /// Numbers of the nodes `overrides` give the binary at `path`
+    fn nodes_running(overrides: &[NodeOverride], path: &str) -> Vec<usize> {
+        overrides
+            .iter()
+            .filter(|node| node.node_path.as_deref() == Some(Path::new(path)))
+            .filter_map(|node| match node.select {
+                NodeSelector::Index(idx) => Some(idx),
+                NodeSelector::Pattern(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn node_mix_spreads_the_binaries_evenly() -> Result<()> {
+        let spec = NetworkSpec {
+            node_mix: vec!["/v2/sn_node=50%".parse()?, "/v3/sn_node=25%".parse()?],
+            ..NetworkSpec::default()
+        };
+        let node_ids: Vec<usize> = (2..=9).collect();
+        let overrides = spec.node_mix_overrides(&node_ids)?;
+
+        // Each half of the nodes runs its share of each binary
+        let v2 = nodes_running(&overrides, "/v2/sn_node");
+        let v3 = nodes_running(&overrides, "/v3/sn_node");
+        for half in [2..=5, 6..=9] {
+            assert_eq!(v2.iter().filter(|idx| half.contains(*idx)).count(), 2);
+            assert_eq!(v3.iter().filter(|idx| half.contains(*idx)).count(), 1);
+        }
+        assert_eq!(overrides.len(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn node_mix_over_100_percent_is_rejected() -> Result<()> {
+        let spec = NetworkSpec {
+            node_mix: vec!["/v2/sn_node=60%".parse()?, "/v3/sn_node=50%".parse()?],
+            ..NetworkSpec::default()
+        };
+        assert!(spec.node_mix_overrides(&[2, 3]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn link_faults_are_parsed() -> Result<()> {
+        let faults: LinkFaults = "latency-msec=50, jitter-msec=10,loss=5%".parse()?;
+        assert_eq!(faults.latency_msec, Some(50));
+        assert_eq!(faults.jitter_msec, Some(10));
+        assert_eq!(faults.loss, Some(5.0));
+        assert!(faults.from.is_none() && faults.to.is_none());
+        assert!(faults.matches(None, "sn-node-2"));
+
+        let faults: LinkFaults = "sn-node-[2-4]->1:duplicate=1,reorder=2".parse()?;
+        assert_eq!((faults.duplicate, faults.reorder), (Some(1.0), Some(2.0)));
+        assert!(faults.matches(Some("sn-node-3"), GENESIS_NODE_NAME));
+        assert!(!faults.matches(Some("sn-node-5"), GENESIS_NODE_NAME));
+        assert!(!faults.matches(None, GENESIS_NODE_NAME));
+        assert!(!faults.matches(Some("sn-node-3"), "sn-node-2"));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_link_faults_are_rejected() {
+        for faults in [
+            "latency-msec",
+            "latency-msec=soon",
+            "loss=101",
+            "duplicate=-1",
+            "bandwidth=10",
+            "2:loss=5",
+            "sn-node-[4-2]->1:loss=5",
+        ] {
+            assert!(faults.parse::<LinkFaults>().is_err(), "{}", faults);
+        }
+    }
+}
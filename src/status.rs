@@ -0,0 +1,132 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result};
+use std::time::{Duration, SystemTime};
+use structopt::StructOpt;
+use tracing::warn;
+
+use crate::{
+    logs,
+    manifest::{unix_time_now, NetworkManifest, NodeRecord},
+    process, NetworkArgs,
+};
+
+/// Report whether each of the nodes of a network started with `launch` is still running
+///
+/// Exits with an error if any of the nodes is down.
+#[derive(Debug, StructOpt)]
+pub struct Status {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+}
+
+impl Status {
+    /// Report the status of the network with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let manifest = NetworkManifest::load(&self.network.nodes_dir()?)?;
+
+        if let Some(name) = &manifest.network_name {
+            println!("Network: {}", name);
+        }
+
+        println!(
+            "{:<20} {:>8} {:<10} {:<16} LAST LOG",
+            "NODE", "PID", "STATE", "UPTIME"
+        );
+
+        let mut down = vec![];
+        for node in &manifest.nodes {
+            let running = process::is_node_running(node.pid, &node.root_dir);
+            if !running {
+                down.push(node.name.as_str());
+            }
+            let state = if !running {
+                "down"
+            } else if process::is_paused(node.pid) {
+                "paused"
+            } else {
+                "running"
+            };
+
+            println!(
+                "{:<20} {:>8} {:<10} {:<16} {}",
+                node.name,
+                node.pid,
+                state,
+                if running {
+                    uptime(node)
+                } else {
+                    "-".to_string()
+                },
+                last_log(node),
+            );
+            if let (false, Some(exit_status)) = (running, &node.exit_status) {
+                println!("{:<20} {}", "", exit_status);
+            }
+        }
+
+        ensure_none_down(&down, manifest.nodes.len())
+    }
+}
+
+/// Fail if any of the nodes of the network of `manifest` is down.
+pub(crate) fn ensure_healthy(manifest: &NetworkManifest) -> Result<()> {
+    let down: Vec<&str> = manifest
+        .nodes
+        .iter()
+        .filter(|node| !process::is_node_running(node.pid, &node.root_dir))
+        .map(|node| node.name.as_str())
+        .collect();
+    ensure_none_down(&down, manifest.nodes.len())
+}
+
+fn ensure_none_down(down: &[&str], total: usize) -> Result<()> {
+    if down.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "{} of {} nodes are down: {}",
+            down.len(),
+            total,
+            down.join(", ")
+        ))
+    }
+}
+
+fn uptime(node: &NodeRecord) -> String {
+    let uptime = Duration::from_secs(unix_time_now().saturating_sub(node.started_at));
+    humantime::format_duration(uptime).to_string()
+}
+
+fn last_log(node: &NodeRecord) -> String {
+    let line = match logs::last_line(&node.root_dir) {
+        Ok(Some(line)) => line,
+        Ok(None) => return "no logs".to_string(),
+        Err(error) => {
+            warn!("{:?}", error);
+            return "unreadable".to_string();
+        }
+    };
+
+    match logs::line_timestamp(&line) {
+        Some(timestamp) => {
+            let ago = SystemTime::now()
+                .duration_since(timestamp)
+                .map(|ago| Duration::from_secs(ago.as_secs()))
+                .unwrap_or_default();
+            format!(
+                "{} ({} ago)",
+                humantime::format_rfc3339_seconds(timestamp),
+                humantime::format_duration(ago)
+            )
+        }
+        None => "unknown".to_string(),
+    }
+}
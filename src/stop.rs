@@ -0,0 +1,204 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::Result;
+use std::{
+    fmt,
+    path::Path,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+use tracing::{debug, info, warn};
+
+use crate::{
+    manifest::{NetworkManifest, NodeRecord},
+    process::{self, Signal},
+    supervisor, NetworkArgs,
+};
+
+/// Time given to nodes which have been sent SIGKILL to disappear
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stop all the nodes of a network started with `launch`
+#[derive(Debug, StructOpt)]
+pub struct Stop {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Time to wait for nodes to exit gracefully before killing them, e.g. "10s"
+    #[structopt(long, default_value = "10s", parse(try_from_str = humantime::parse_duration))]
+    timeout: Duration,
+}
+
+impl Stop {
+    /// Stop the network with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let stopped = stop_network(&self.network.nodes_dir()?, self.timeout)?;
+        print_summary(&stopped);
+        Ok(())
+    }
+}
+
+pub(crate) fn print_summary(stopped: &[StoppedNode]) {
+    for node in stopped {
+        println!("{:<20} {}", node.name, node.outcome);
+    }
+}
+
+/// How a node was brought down by [`stop_network`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopOutcome {
+    /// The node was not running in the first place
+    NotRunning,
+    /// The node exited cleanly after SIGTERM
+    Exited,
+    /// The node didn't exit in time and was sent SIGKILL
+    Killed,
+    /// The node was still running even after being sent SIGKILL
+    StillRunning,
+}
+
+impl fmt::Display for StopOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotRunning => write!(f, "not running"),
+            Self::Exited => write!(f, "exited cleanly"),
+            Self::Killed => write!(f, "killed"),
+            Self::StillRunning => write!(f, "still running"),
+        }
+    }
+}
+
+/// Result of stopping a single node
+#[derive(Clone, Debug)]
+pub struct StoppedNode {
+    /// Name of the node, e.g. `sn-node-4`
+    pub name: String,
+    /// PID the node was running with
+    pub pid: u32,
+    /// How the node was brought down
+    pub outcome: StopOutcome,
+}
+
+/// Stop all the nodes recorded in the manifest of the network at `nodes_dir`.
+///
+/// Nodes are sent SIGTERM and given `timeout` to exit before being sent SIGKILL. If the network is
+/// being supervised by a launcher running in the foreground, the launcher is asked to shut it down
+/// instead, so it doesn't restart the nodes.
+pub fn stop_network(nodes_dir: &Path, timeout: Duration) -> Result<Vec<StoppedNode>> {
+    let manifest = NetworkManifest::load(nodes_dir)?;
+    let nodes: Vec<&NodeRecord> = manifest.nodes.iter().collect();
+
+    match supervisor::running_supervisor(&manifest) {
+        Some(pid) => {
+            info!(
+                "Asking the launcher supervising the network (pid {}) to shut it down...",
+                pid
+            );
+            process::send_signal(pid, Signal::Terminate)?;
+            Ok(stop_nodes(&nodes, timeout, false))
+        }
+        None => Ok(stop_nodes(&nodes, timeout, true)),
+    }
+}
+
+/// Stop the given nodes, SIGTERM first and SIGKILL if they don't exit within `timeout`.
+///
+/// If `terminate` is false the nodes are expected to be asked to exit by someone else, and are
+/// only waited for (and killed if need be).
+pub(crate) fn stop_nodes(
+    nodes: &[&NodeRecord],
+    timeout: Duration,
+    terminate: bool,
+) -> Vec<StoppedNode> {
+    let mut running = vec![];
+    let mut stopped = vec![];
+
+    for node in nodes {
+        if !process::is_node_running(node.pid, &node.root_dir) {
+            debug!("{} (pid {}) is not running", node.name, node.pid);
+            stopped.push(StoppedNode::new(node, StopOutcome::NotRunning));
+            continue;
+        }
+
+        if !terminate {
+            running.push(*node);
+            continue;
+        }
+
+        info!("Stopping {} (pid {})...", node.name, node.pid);
+        match process::signal_node(node.pid, Signal::Terminate) {
+            Ok(()) => {
+                resume_if_paused(node.pid);
+                running.push(*node)
+            }
+            Err(error) => {
+                warn!("{:?}", error);
+                stopped.push(StoppedNode::new(node, StopOutcome::StillRunning));
+            }
+        }
+    }
+
+    // Nodes are stopped concurrently, so the timeout applies to all of them at once
+    let mut remaining = timeout;
+    for node in running {
+        let started = Instant::now();
+        let outcome = if process::wait_for_exit(node.pid, &node.root_dir, remaining) {
+            StopOutcome::Exited
+        } else {
+            warn!(
+                "{} (pid {}) didn't exit within {:?}, killing it",
+                node.name, node.pid, timeout
+            );
+            kill_node(node)
+        };
+        remaining = remaining.saturating_sub(started.elapsed());
+        stopped.push(StoppedNode::new(node, outcome));
+    }
+
+    // Report in the order the nodes were given to us
+    stopped.sort_by_key(|stopped| {
+        nodes
+            .iter()
+            .position(|node| node.name == stopped.name && node.pid == stopped.pid)
+    });
+    stopped
+}
+
+/// Kill the node outright, waiting for it to be gone.
+pub(crate) fn kill_node(node: &NodeRecord) -> StopOutcome {
+    if let Err(error) = process::signal_node(node.pid, Signal::Kill) {
+        warn!("{:?}", error);
+    }
+
+    if process::wait_for_exit(node.pid, &node.root_dir, KILL_TIMEOUT) {
+        StopOutcome::Killed
+    } else {
+        StopOutcome::StillRunning
+    }
+}
+
+impl StoppedNode {
+    fn new(node: &NodeRecord, outcome: StopOutcome) -> Self {
+        Self {
+            name: node.name.clone(),
+            pid: node.pid,
+            outcome,
+        }
+    }
+}
+
+/// Resume the node `pid` if it's been paused, so it handles the signal it's just been sent.
+pub(crate) fn resume_if_paused(pid: u32) {
+    if process::is_paused(pid) {
+        if let Err(error) = process::signal_node(pid, Signal::Continue) {
+            warn!("{:?}", error);
+        }
+    }
+}
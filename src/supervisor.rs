@@ -0,0 +1,467 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result};
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    process::{Child, ExitStatus},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    cmd,
+    manifest::{unix_time_now, NetworkManifest, NodeRecord},
+    process::{self, Signal},
+    restart,
+    stop::{self, StopOutcome, StoppedNode},
+};
+
+/// How often the supervisor checks on the nodes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Longest delay before restarting a node which keeps exiting
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Set once the launcher has been asked to shut down, by SIGINT or SIGTERM
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Install a handler for SIGINT and SIGTERM which asks the launcher to shut the nodes down.
+pub(crate) fn handle_shutdown_signals() -> Result<()> {
+    match ctrlc::set_handler(|| {
+        if !SHUTDOWN.swap(true, Ordering::SeqCst) {
+            info!("Shutdown requested");
+        }
+    }) {
+        // The handler is process-wide, so it may have been installed by an earlier launch already
+        Ok(()) | Err(ctrlc::Error::MultipleHandlers) => Ok(()),
+        Err(error) => Err(eyre!(error)),
+    }
+}
+
+/// Ask the launcher to shut down, as SIGINT or SIGTERM would.
+pub(crate) fn request_shutdown() {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+/// Forget about the launcher having been asked to shut down, once it has.
+pub(crate) fn clear_shutdown() {
+    SHUTDOWN.store(false, Ordering::SeqCst);
+}
+
+/// Whether the launcher has been asked to shut down
+pub(crate) fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// Fail if the launcher has been asked to shut down.
+pub(crate) fn ensure_not_interrupted() -> Result<()> {
+    if shutdown_requested() {
+        Err(eyre!("Launch interrupted"))
+    } else {
+        Ok(())
+    }
+}
+
+/// PID of the launcher supervising the network of `manifest`, if it's still running
+pub(crate) fn running_supervisor(manifest: &NetworkManifest) -> Option<u32> {
+    let pid = manifest.supervisor_pid?;
+    let start_time = manifest.supervisor_start_time.as_deref()?;
+    process::is_launcher_running(pid, start_time).then_some(pid)
+}
+
+/// Fail if the network of `manifest` is being supervised by a launcher, which would fight over
+/// its nodes (e.g. restart those being stopped) and overwrite its manifest.
+pub(crate) fn ensure_not_supervised(manifest: &NetworkManifest) -> Result<()> {
+    match running_supervisor(manifest) {
+        Some(pid) => Err(eyre!(
+            "The network is being supervised by a launcher (pid {}), stop it first",
+            pid
+        )),
+        None => Ok(()),
+    }
+}
+
+/// When to restart a node which exited while being supervised
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Never restart nodes
+    Never,
+    /// Restart nodes which exited with a non-zero status or were killed by a signal
+    OnFailure,
+    /// Restart nodes whenever they exit
+    Always,
+}
+
+impl RestartPolicy {
+    fn should_restart(&self, status: ExitStatus) -> bool {
+        match self {
+            Self::Never => false,
+            Self::OnFailure => !status.success(),
+            Self::Always => true,
+        }
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "never" => Ok(Self::Never),
+            "on-failure" => Ok(Self::OnFailure),
+            "always" => Ok(Self::Always),
+            other => Err(eyre!(
+                "Invalid restart policy '{}', expected one of: never, on-failure, always",
+                other
+            )),
+        }
+    }
+}
+
+/// Keeps hold of the nodes' processes, reaping and (depending on the policy) restarting them, and
+/// keeps the network manifest up to date along the way.
+pub(crate) struct Supervisor {
+    nodes_dir: PathBuf,
+    manifest: NetworkManifest,
+    policy: RestartPolicy,
+    max_restarts: u32,
+    backoff: Duration,
+    shutdown_timeout: Duration,
+    nodes: Vec<SupervisedNode>,
+}
+
+struct SupervisedNode {
+    name: String,
+    child: Option<Child>,
+    restarts: u32,
+    restart_at: Option<Instant>,
+}
+
+impl Supervisor {
+    pub(crate) fn new(
+        nodes_dir: PathBuf,
+        manifest: NetworkManifest,
+        policy: RestartPolicy,
+        max_restarts: u32,
+        backoff: Duration,
+        shutdown_timeout: Duration,
+    ) -> Self {
+        Self {
+            nodes_dir,
+            manifest,
+            policy,
+            max_restarts,
+            backoff,
+            shutdown_timeout,
+            nodes: vec![],
+        }
+    }
+
+    /// Record the contacts and genesis key of the network in the manifest.
+    pub(crate) fn set_network_info(
+        &mut self,
+        contacts: Vec<SocketAddr>,
+        genesis_key: String,
+    ) -> Result<()> {
+        self.manifest.contacts = contacts;
+        self.manifest.genesis_key = Some(genesis_key);
+        self.manifest.save(&self.nodes_dir)
+    }
+
+    /// Record the node in the manifest and keep hold of its process.
+    pub(crate) fn add(&mut self, record: NodeRecord, child: Child) -> Result<()> {
+        self.nodes.push(SupervisedNode {
+            name: record.name.clone(),
+            child: Some(child),
+            restarts: 0,
+            restart_at: None,
+        });
+        self.manifest.add_node(record);
+        self.manifest.save(&self.nodes_dir)
+    }
+
+    /// Hand over the processes of the nodes, e.g. to the program embedding the launcher, instead
+    /// of watching over them.
+    pub(crate) fn into_children(self) -> Vec<(String, Child)> {
+        self.nodes
+            .into_iter()
+            .filter_map(|node| Some((node.name, node.child?)))
+            .collect()
+    }
+
+    /// Watch over the nodes until all of them have exited for good, or until the launcher is
+    /// asked to shut down.
+    pub(crate) fn run(mut self) -> Result<()> {
+        info!(
+            "Supervising {} nodes (restart policy: {:?}), press Ctrl-C to shut them down",
+            self.nodes.len(),
+            self.policy
+        );
+
+        // Let `stop` know it should ask us to shut down, rather than fight our restart policy
+        let pid = std::process::id();
+        self.manifest.supervisor_start_time = Some(
+            process::start_time(pid)
+                .ok_or_else(|| eyre!("Failed to find out when the launcher started"))?,
+        );
+        self.manifest.supervisor_pid = Some(pid);
+        self.manifest.save(&self.nodes_dir)?;
+
+        loop {
+            if shutdown_requested() {
+                return self.shutdown();
+            }
+
+            // Only write the manifest when a node changed state, so the supervisor doesn't keep
+            // clobbering it
+            let reaped = self.reap()?;
+            let restarted = self.restart_due();
+            if reaped || restarted {
+                self.manifest.save(&self.nodes_dir)?;
+            }
+
+            if self
+                .nodes
+                .iter()
+                .all(|node| node.child.is_none() && node.restart_at.is_none())
+            {
+                info!("All supervised nodes have exited");
+                self.manifest.supervisor_pid = None;
+                self.manifest.supervisor_start_time = None;
+                return self.manifest.save(&self.nodes_dir);
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Ask all the nodes to exit, killing those which don't do so in time, and print a summary.
+    pub(crate) fn shutdown(mut self) -> Result<()> {
+        let live = self
+            .nodes
+            .iter()
+            .filter(|node| node.child.is_some())
+            .count();
+        info!("Shutting down {} nodes...", live);
+
+        for node in &self.nodes {
+            if let Some(child) = &node.child {
+                // `cargo flamegraph` only writes out the graph when interrupted
+                let signal = match self.manifest.node(&node.name) {
+                    Some(record) if record.flame => Signal::Interrupt,
+                    _ => Signal::Terminate,
+                };
+                if let Err(error) = process::signal_node(child.id(), signal) {
+                    warn!("{:?}", error);
+                }
+                stop::resume_if_paused(child.id());
+            }
+        }
+
+        let deadline = Instant::now() + self.shutdown_timeout;
+        let manifest = &mut self.manifest;
+        let mut stopped = vec![];
+        for node in &mut self.nodes {
+            let (pid, outcome) = match node.child.take() {
+                Some(child) => (
+                    child.id(),
+                    stop_child(&node.name, child, deadline, manifest),
+                ),
+                None => (
+                    manifest.node(&node.name).map_or(0, |record| record.pid),
+                    StopOutcome::NotRunning,
+                ),
+            };
+            stopped.push(StoppedNode {
+                name: node.name.clone(),
+                pid,
+                outcome,
+            });
+        }
+
+        self.manifest.supervisor_pid = None;
+        self.manifest.supervisor_start_time = None;
+        self.manifest.save(&self.nodes_dir)?;
+
+        stop::print_summary(&stopped);
+        Ok(())
+    }
+
+    /// Reap the nodes which exited, scheduling their restart as per the policy. Returns whether
+    /// any node did exit.
+    fn reap(&mut self) -> Result<bool> {
+        let mut reaped = false;
+        for node in &mut self.nodes {
+            let status = match node.child.as_mut().map(Child::try_wait).transpose()? {
+                Some(Some(status)) => status,
+                _ => continue,
+            };
+            node.child = None;
+            reaped = true;
+
+            if status.success() {
+                info!("{} exited ({})", node.name, status);
+            } else {
+                error!("{} crashed ({})", node.name, status);
+            }
+
+            if let Some(record) = self.manifest.node_mut(&node.name) {
+                record.exit_status = Some(status.to_string());
+            }
+
+            if !self.policy.should_restart(status) {
+                continue;
+            }
+
+            if let Err(error) = restart::ensure_not_genesis(&node.name) {
+                warn!("{}", error);
+                continue;
+            }
+
+            if node.restarts >= self.max_restarts {
+                warn!(
+                    "Not restarting {}, it has already been restarted {} times",
+                    node.name, node.restarts
+                );
+                continue;
+            }
+
+            // Back off exponentially, so a node which keeps crashing doesn't spin
+            let backoff = restart_backoff(self.backoff, node.restarts);
+            info!("Restarting {} in {:?}", node.name, backoff);
+            node.restart_at = Some(Instant::now() + backoff);
+        }
+
+        Ok(reaped)
+    }
+
+    /// Restart the nodes whose restart is due. Returns whether any node was restarted.
+    fn restart_due(&mut self) -> bool {
+        let mut restarted = false;
+        let now = Instant::now();
+        for node in &mut self.nodes {
+            match node.restart_at {
+                Some(restart_at) if restart_at <= now => node.restart_at = None,
+                _ => continue,
+            }
+
+            let record = match self.manifest.node_mut(&node.name) {
+                Some(record) => record,
+                None => continue,
+            };
+
+            node.restarts += 1;
+            restarted = true;
+            match cmd::spawn_recorded(record) {
+                Ok(child) => {
+                    debug!("{} restarted with pid {}", node.name, child.id());
+                    record.pid = child.id();
+                    record.started_at = unix_time_now();
+                    record.exit_status = None;
+                    node.child = Some(child);
+                }
+                Err(error) => error!("Failed to restart {}: {:?}", node.name, error),
+            }
+        }
+
+        restarted
+    }
+}
+
+/// Delay before restarting a node which has already been restarted `restarts` times: `base`
+/// doubled on each restart, up to `MAX_RESTART_BACKOFF`.
+fn restart_backoff(base: Duration, restarts: u32) -> Duration {
+    base.checked_mul(2u32.saturating_pow(restarts))
+        .unwrap_or(MAX_RESTART_BACKOFF)
+        .min(MAX_RESTART_BACKOFF)
+}
+
+/// Wait until `deadline` for a node which has been signalled to exit, killing it otherwise.
+fn stop_child(
+    name: &str,
+    mut child: Child,
+    deadline: Instant,
+    manifest: &mut NetworkManifest,
+) -> StopOutcome {
+    let (status, outcome) = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break (Some(status), StopOutcome::Exited),
+            Ok(None) if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                warn!("{} didn't exit in time, killing it", name);
+                if let Err(error) = process::signal_node(child.id(), Signal::Kill) {
+                    warn!("{:?}", error);
+                }
+                break (child.wait().ok(), StopOutcome::Killed);
+            }
+            Err(error) => {
+                warn!("Failed to wait for {}: {:?}", name, error);
+                break (None, StopOutcome::StillRunning);
+            }
+        }
+    };
+
+    if let (Some(status), Some(record)) = (status, manifest.node_mut(name)) {
+        record.exit_status = Some(status.to_string());
+    }
+
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_policy_parses() {
+        assert_eq!(
+            "never".parse::<RestartPolicy>().ok(),
+            Some(RestartPolicy::Never)
+        );
+        assert_eq!(
+            "on-failure".parse::<RestartPolicy>().ok(),
+            Some(RestartPolicy::OnFailure)
+        );
+        assert_eq!(
+            "always".parse::<RestartPolicy>().ok(),
+            Some(RestartPolicy::Always)
+        );
+        assert!("sometimes".parse::<RestartPolicy>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restart_policy_decides_on_exit_status() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let success = ExitStatus::from_raw(0);
+        let failure = ExitStatus::from_raw(1 << 8);
+        assert!(!RestartPolicy::Never.should_restart(failure));
+        assert!(!RestartPolicy::OnFailure.should_restart(success));
+        assert!(RestartPolicy::OnFailure.should_restart(failure));
+        assert!(RestartPolicy::Always.should_restart(success));
+    }
+
+    #[test]
+    fn restart_backoff_doubles_up_to_a_cap() {
+        let base = Duration::from_secs(1);
+        assert_eq!(restart_backoff(base, 0), base);
+        assert_eq!(restart_backoff(base, 3), Duration::from_secs(8));
+        assert_eq!(restart_backoff(base, 40), MAX_RESTART_BACKOFF);
+        assert_eq!(
+            restart_backoff(Duration::from_secs(u64::MAX), 1),
+            MAX_RESTART_BACKOFF
+        );
+    }
+}
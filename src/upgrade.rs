@@ -0,0 +1,144 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result, WrapErr};
+use regex::Regex;
+use std::{path::PathBuf, time::Duration};
+use structopt::StructOpt;
+use tracing::{info, warn};
+
+use crate::{
+    absolute_program,
+    cmd::NodeCmd,
+    manifest::{NetworkManifest, NodeRecord},
+    proxy, restart, status, supervisor,
+    wait::DEFAULT_JOIN_REGEX,
+    NetworkArgs, GENESIS_NODE_NAME,
+};
+
+/// Upgrade the nodes of a running network to another sn_node binary, one node at a time
+///
+/// Each node is stopped and started again from the same root dir with the new binary, and has to
+/// rejoin the network before the next one is upgraded. The upgrade is aborted as soon as a node
+/// fails to rejoin or any of the other nodes goes down. The genesis node keeps running its binary,
+/// as started again it would found a new network rather than rejoin this one.
+#[derive(Debug, StructOpt)]
+pub struct Upgrade {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Path of the sn_node binary to upgrade the nodes to
+    #[structopt(short = "p", long)]
+    node_path: PathBuf,
+
+    /// Time given to each upgraded node to rejoin the network, e.g. "60s"
+    #[structopt(long, default_value = "60s", parse(try_from_str = humantime::parse_duration))]
+    rejoin_timeout: Duration,
+
+    /// Time to wait for each node to exit gracefully before killing it, e.g. "10s"
+    #[structopt(long, default_value = "10s", parse(try_from_str = humantime::parse_duration))]
+    stop_timeout: Duration,
+
+    /// Regex matching the log line a node writes once it has rejoined the network
+    #[structopt(long, default_value = DEFAULT_JOIN_REGEX)]
+    join_regex: Regex,
+}
+
+impl Upgrade {
+    /// Upgrade the network with these arguments.
+    pub fn run(&self) -> Result<()> {
+        let nodes_dir = self.network.nodes_dir()?;
+        let mut manifest = NetworkManifest::load(&nodes_dir)?;
+        supervisor::ensure_not_supervised(&manifest)?;
+        proxy::ensure_not_relayed(&manifest)?;
+        status::ensure_healthy(&manifest)?;
+
+        // Recorded as the binary to restart the nodes with, from wherever that's done
+        let node_path = absolute_program(&self.node_path)?;
+        let program = node_path.display().to_string();
+        let version = NodeCmd::new(node_path.as_path()).version()?;
+        info!("Upgrading the network to {} from {}", version, program);
+
+        // The genesis node can only be started with `--first`, founding a new network
+        let names: Vec<String> = manifest
+            .nodes
+            .iter()
+            .map(|node| node.name.clone())
+            .filter(|name| name != GENESIS_NODE_NAME)
+            .collect();
+        if manifest.node(GENESIS_NODE_NAME).is_some() {
+            warn!(
+                "{} can't be restarted without founding a new network, leaving it as it is",
+                GENESIS_NODE_NAME
+            );
+        }
+
+        let mut upgraded = vec![];
+        for name in &names {
+            let node = manifest
+                .node(name)
+                .cloned()
+                .ok_or_else(|| eyre!("{} is missing from the network manifest", name))?;
+
+            if node.program == program && node.node_version == version {
+                info!("{} already runs {}", name, version);
+                continue;
+            }
+            if node.flame {
+                return Err(eyre!(
+                    "{} runs under `cargo flamegraph`, it can't be upgraded to another binary",
+                    name
+                ));
+            }
+
+            let record = NodeRecord {
+                program: program.clone(),
+                node_version: version.clone(),
+                ..node.clone()
+            };
+            let result = restart::relaunch_node(
+                &nodes_dir,
+                &mut manifest,
+                record,
+                &[],
+                self.stop_timeout,
+                &self.join_regex,
+                self.rejoin_timeout,
+            )
+            .and_then(|_| status::ensure_healthy(&manifest));
+
+            if let Err(error) = result {
+                print_summary(&upgraded);
+                return Err(error).wrap_err_with(|| {
+                    format!(
+                        "Upgrade aborted at {}, after upgrading {} of {} nodes",
+                        name,
+                        upgraded.len(),
+                        names.len()
+                    )
+                });
+            }
+
+            info!(
+                "Upgraded {} from {} to {}",
+                name, node.node_version, version
+            );
+            upgraded.push((node.name, node.node_version, version.clone()));
+        }
+
+        print_summary(&upgraded);
+        Ok(())
+    }
+}
+
+fn print_summary(upgraded: &[(String, String, String)]) {
+    for (name, from, to) in upgraded {
+        println!("{:<20} {} -> {}", name, from, to);
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright 2022 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use eyre::{eyre, Result};
+use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use structopt::StructOpt;
+use tracing::{debug, info};
+
+use crate::{
+    logs::{self, LogCursor},
+    manifest::NetworkManifest,
+    process, NetworkArgs, GENESIS_NODE_NAME,
+};
+
+/// Log lines marking a node as having joined the network, or having been relocated within it
+pub(crate) const DEFAULT_JOIN_REGEX: &str = "ReceivedJoinApproved|RelocateEnd|Joined the network";
+
+/// How often the node logs are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Wait until a number of the nodes of a network have joined it
+///
+/// Exits with an error listing the nodes which haven't joined if that doesn't happen in time.
+#[derive(Debug, StructOpt)]
+pub struct Wait {
+    #[structopt(flatten)]
+    network: NetworkArgs,
+
+    /// Number of nodes, including the genesis node, which are expected to join the network
+    #[structopt(short = "n", long)]
+    nodes: usize,
+
+    /// Time to wait for the nodes to join, e.g. "5m"
+    #[structopt(short = "t", long, default_value = "5m", parse(try_from_str = humantime::parse_duration))]
+    timeout: Duration,
+
+    /// Regex matching the log line a node writes once it has joined the network (or been
+    /// relocated within it)
+    #[structopt(long, default_value = DEFAULT_JOIN_REGEX)]
+    join_regex: Regex,
+}
+
+impl Wait {
+    /// Wait for the network with these arguments.
+    pub fn run(&self) -> Result<()> {
+        wait_for_joined(
+            &self.network.nodes_dir()?,
+            self.nodes,
+            self.timeout,
+            &self.join_regex,
+        )?;
+        println!("{} nodes have joined the network", self.nodes);
+        Ok(())
+    }
+}
+
+/// A running node of the network being waited for
+struct Member {
+    /// PID the node was found running with, which changes if it's restarted
+    pid: u32,
+    /// When the node was started, any earlier log lines being left by a previous run
+    started_at: SystemTime,
+    cursor: LogCursor,
+    joined: bool,
+}
+
+/// Wait until `expected` nodes of the network in `nodes_dir` have logged a line matching
+/// `join_regex` since they were started.
+///
+/// Only the nodes of the manifest which are running count, so the logs left by stopped or removed
+/// nodes, or by an earlier network in the same dir, aren't taken for joins. The genesis node founds
+/// the network rather than joining it, so it counts as a member as soon as it has logged anything.
+pub(crate) fn wait_for_joined(
+    nodes_dir: &Path,
+    expected: usize,
+    timeout: Duration,
+    join_regex: &Regex,
+) -> Result<()> {
+    info!("Waiting for {} nodes to join the network...", expected);
+
+    let started = Instant::now();
+    let mut nodes: BTreeMap<String, Member> = BTreeMap::new();
+
+    loop {
+        // Nodes may still be being launched or restarted, so look out for new ones every time
+        let manifest = NetworkManifest::load_or_new(nodes_dir)?;
+        let mut running = BTreeMap::new();
+        for record in &manifest.nodes {
+            if !process::is_node_running(record.pid, &record.root_dir) {
+                continue;
+            }
+            let member = match nodes.remove(&record.name) {
+                Some(member) if member.pid == record.pid => member,
+                _ => Member {
+                    pid: record.pid,
+                    started_at: UNIX_EPOCH + Duration::from_secs(record.started_at),
+                    cursor: LogCursor::from_start(&record.root_dir),
+                    joined: false,
+                },
+            };
+            let _ = running.insert(record.name.clone(), member);
+        }
+        nodes = running;
+
+        for (name, member) in &mut nodes {
+            if member.joined {
+                continue;
+            }
+
+            let started_at = member.started_at;
+            let lines: Vec<String> = member
+                .cursor
+                .new_lines()?
+                .into_iter()
+                .filter(|line| {
+                    logs::line_timestamp(line)
+                        .filter(|timestamp| *timestamp < started_at)
+                        .is_none()
+                })
+                .collect();
+            member.joined = if name == GENESIS_NODE_NAME {
+                !lines.is_empty()
+            } else {
+                lines
+                    .iter()
+                    .any(|line| join_regex.is_match(&logs::line_message(line)))
+            };
+            if member.joined {
+                debug!("{} has joined the network", name);
+            }
+        }
+
+        let joined = nodes.values().filter(|member| member.joined).count();
+        if joined >= expected {
+            return Ok(());
+        }
+
+        if started.elapsed() >= timeout {
+            let stragglers: Vec<&str> = nodes
+                .iter()
+                .filter(|(_, member)| !member.joined)
+                .map(|(name, _)| name.as_str())
+                .collect();
+
+            return Err(eyre!(
+                "Only {} of {} expected nodes joined the network within {:?} ({} running nodes found). \
+                 Nodes which haven't joined: {}",
+                joined,
+                expected,
+                timeout,
+                nodes.len(),
+                if stragglers.is_empty() {
+                    "none".to_string()
+                } else {
+                    stragglers.join(", ")
+                }
+            ));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::manifest::NodeRecord;
+    use std::{
+        fs,
+        process::{Child, Command},
+    };
+
+    /// Stub node processes, standing in for the nodes of a network with their root dirs on their
+    /// command lines, killed once the test is done
+    struct Nodes {
+        dir: tempfile::TempDir,
+        manifest: NetworkManifest,
+        children: Vec<Child>,
+    }
+
+    impl Nodes {
+        fn new() -> Result<Self> {
+            Ok(Self {
+                dir: tempfile::tempdir()?,
+                manifest: NetworkManifest::new(),
+                children: vec![],
+            })
+        }
+
+        fn path(&self) -> &Path {
+            self.dir.path()
+        }
+
+        /// Start the stub node `name`, recorded in the manifest, with the given log lines.
+        fn start(&mut self, name: &str, lines: &[&str]) -> Result<()> {
+            let root_dir = self.path().join(name);
+            log(self.path(), name, lines)?;
+            let child = Command::new("sh")
+                .args(["-c", "sleep 30", "sh"])
+                .arg(&root_dir)
+                .spawn()?;
+            self.manifest.add_node(NodeRecord {
+                name: name.to_string(),
+                pid: child.id(),
+                root_dir,
+                program: "sh".to_string(),
+                args: vec![],
+                envs: vec![],
+                current_dir: None,
+                flame: false,
+                started_at: crate::manifest::unix_time_now(),
+                node_version: "sn_node 0.1.0".to_string(),
+                exit_status: None,
+            });
+            self.children.push(child);
+            self.manifest.save(self.dir.path())
+        }
+    }
+
+    impl Drop for Nodes {
+        fn drop(&mut self) {
+            for child in &mut self.children {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+
+    /// Write the log of the node `name`, each line logged now unless it starts with a timestamp.
+    fn log(nodes_dir: &Path, name: &str, lines: &[&str]) -> Result<()> {
+        let now = humantime::format_rfc3339_micros(SystemTime::now()).to_string();
+        let dir = nodes_dir.join(name);
+        fs::create_dir_all(&dir)?;
+        let content: String = lines
+            .iter()
+            .map(|line| {
+                if line.starts_with('{') || line.starts_with("20") {
+                    format!("{}\n", line)
+                } else {
+                    format!("{} {}\n", now, line)
+                }
+            })
+            .collect();
+        fs::write(dir.join("sn_node.log.2022"), content)?;
+        Ok(())
+    }
+
+    fn stragglers(nodes_dir: &Path, expected: usize) -> String {
+        let join_regex = Regex::new(DEFAULT_JOIN_REGEX).expect("valid join regex");
+        wait_for_joined(nodes_dir, expected, Duration::ZERO, &join_regex)
+            .err()
+            .map(|error| error.to_string())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn nodes_join_on_a_matching_log_line() -> Result<()> {
+        let mut nodes = Nodes::new()?;
+        nodes.start(GENESIS_NODE_NAME, &["INFO Starting"])?;
+        nodes.start("sn-node-2", &["INFO Joined the network"])?;
+        nodes.start("sn-node-3", &["{\"fields\":{\"message\":\"RelocateEnd\"}}"])?;
+        nodes.start("sn-node-4", &["INFO Bootstrapping"])?;
+        let join_regex = Regex::new(DEFAULT_JOIN_REGEX)?;
+
+        wait_for_joined(nodes.path(), 3, Duration::ZERO, &join_regex)?;
+
+        let error = stragglers(nodes.path(), 4);
+        assert!(error.contains("haven't joined: sn-node-4"), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn stale_logs_and_nodes_which_are_not_running_do_not_count() -> Result<()> {
+        let mut nodes = Nodes::new()?;
+        // Logged by an earlier network in the same dir
+        nodes.start(
+            GENESIS_NODE_NAME,
+            &["2020-01-01T00:00:00.000000Z INFO Starting"],
+        )?;
+        nodes.start(
+            "sn-node-2",
+            &["2020-01-01T00:00:00.000000Z INFO Joined the network"],
+        )?;
+        // Left by a removed node, which isn't in the manifest
+        log(nodes.path(), "sn-node-3", &["INFO Joined the network"])?;
+
+        let error = stragglers(nodes.path(), 1);
+        assert!(
+            error.contains("(2 running nodes found)")
+                && error.contains("haven't joined: sn-node-2, sn-node-genesis"),
+            "{}",
+            error
+        );
+
+        // Only the lines logged since the nodes were started count
+        log(
+            nodes.path(),
+            "sn-node-2",
+            &[
+                "2020-01-01T00:00:00.000000Z INFO Joined the network",
+                "INFO Joined the network",
+            ],
+        )?;
+        let error = stragglers(nodes.path(), 2);
+        assert!(
+            error.contains("haven't joined: sn-node-genesis"),
+            "{}",
+            error
+        );
+
+        // Nodes which are no longer running don't count either
+        for child in &mut nodes.children {
+            child.kill()?;
+            let _ = child.wait()?;
+        }
+        let error = stragglers(nodes.path(), 1);
+        assert!(error.contains("(0 running nodes found)"), "{}", error);
+        Ok(())
+    }
+}